@@ -2,6 +2,23 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_user_table;
 mod m20220101_000002_create_txt_table;
+mod m20220101_000003_add_txt_format;
+mod m20220101_000004_add_txt_encoding;
+mod m20220101_000005_create_txt_version_table;
+mod m20220101_000006_add_txt_deleted_at;
+mod m20220101_000007_add_txt_metadata;
+mod m20220101_000008_create_stopword_table;
+mod m20220101_000009_create_user_dict_table;
+mod m20220101_000010_create_synonym_table;
+mod m20220101_000011_add_txt_simhash;
+mod m20220101_000012_create_tag_table;
+mod m20220101_000013_create_txt_tag_table;
+mod m20220101_000014_create_folder_table;
+mod m20220101_000015_add_txt_folder;
+mod m20220101_000016_create_user_group_table;
+mod m20220101_000017_create_user_group_member_table;
+mod m20220101_000018_create_txt_grant_table;
+mod m20220101_000019_index_txt_simhash;
 
 pub struct Migrator;
 
@@ -10,6 +27,24 @@ impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             Box::new(m20220101_000001_create_user_table::Migration),
-            Box::new(m20220101_000002_create_txt_table::Migration)]
+            Box::new(m20220101_000002_create_txt_table::Migration),
+            Box::new(m20220101_000003_add_txt_format::Migration),
+            Box::new(m20220101_000004_add_txt_encoding::Migration),
+            Box::new(m20220101_000005_create_txt_version_table::Migration),
+            Box::new(m20220101_000006_add_txt_deleted_at::Migration),
+            Box::new(m20220101_000007_add_txt_metadata::Migration),
+            Box::new(m20220101_000008_create_stopword_table::Migration),
+            Box::new(m20220101_000009_create_user_dict_table::Migration),
+            Box::new(m20220101_000010_create_synonym_table::Migration),
+            Box::new(m20220101_000011_add_txt_simhash::Migration),
+            Box::new(m20220101_000012_create_tag_table::Migration),
+            Box::new(m20220101_000013_create_txt_tag_table::Migration),
+            Box::new(m20220101_000014_create_folder_table::Migration),
+            Box::new(m20220101_000015_add_txt_folder::Migration),
+            Box::new(m20220101_000016_create_user_group_table::Migration),
+            Box::new(m20220101_000017_create_user_group_member_table::Migration),
+            Box::new(m20220101_000018_create_txt_grant_table::Migration),
+            Box::new(m20220101_000019_index_txt_simhash::Migration),
+        ]
     }
 }
@@ -48,5 +48,22 @@ pub enum Txt {
     Title,
     Hash,
     UserId,
-    Level
+    Level,
+    // m20220101_000003
+    Format,
+    // m20220101_000004
+    Encoding,
+    // m20220101_000006
+    DeletedAt,
+    // m20220101_000007
+    CreatedAt,
+    UpdatedAt,
+    SizeBytes,
+    CharCount,
+    // m20220101_000011
+    Simhash,
+    // m20220101_000015
+    FolderId,
+    // m20220101_000019
+    SimhashSkipped,
 }
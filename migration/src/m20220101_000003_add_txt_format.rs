@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(
+                        ColumnDef::new(Txt::Format)
+                            .string_len(16)
+                            .not_null()
+                            .default("plain"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_column(Txt::Format)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TxtVersion::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TxtVersion::Id)
+                            .big_unsigned()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TxtVersion::TxtId).big_unsigned().not_null())
+                    .col(ColumnDef::new(TxtVersion::Hash).string_len(64).unique_key().not_null())
+                    .col(ColumnDef::new(TxtVersion::Format).string_len(16).not_null().default("plain"))
+                    .col(ColumnDef::new(TxtVersion::Encoding).string_len(32).null())
+                    .col(
+                        ColumnDef::new(TxtVersion::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_version-txt-id")
+                        .from(TxtVersion::Table, TxtVersion::TxtId)
+                        .to(Txt::Table, Txt::Id)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TxtVersion::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TxtVersion {
+    Table,
+    Id,
+    TxtId,
+    Hash,
+    Format,
+    Encoding,
+    CreatedAt,
+    // m20220101_000007
+    SizeBytes,
+    CharCount,
+    // m20220101_000011
+    Simhash,
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(ColumnDef::new(Txt::DeletedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_column(Txt::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+use super::m20220101_000005_create_txt_version_table::TxtVersion;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(
+                        ColumnDef::new(Txt::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column(
+                        ColumnDef::new(Txt::UpdatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column(ColumnDef::new(Txt::SizeBytes).big_unsigned().not_null().default(0))
+                    .add_column(ColumnDef::new(Txt::CharCount).big_unsigned().not_null().default(0))
+                    .to_owned(),
+            )
+            .await?;
+        // 恢复历史版本时需要其大小
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TxtVersion::Table)
+                    .add_column(ColumnDef::new(TxtVersion::SizeBytes).big_unsigned().not_null().default(0))
+                    .add_column(ColumnDef::new(TxtVersion::CharCount).big_unsigned().not_null().default(0))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TxtVersion::Table)
+                    .drop_column(TxtVersion::SizeBytes)
+                    .drop_column(TxtVersion::CharCount)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_column(Txt::CreatedAt)
+                    .drop_column(Txt::UpdatedAt)
+                    .drop_column(Txt::SizeBytes)
+                    .drop_column(Txt::CharCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserDict::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserDict::Id)
+                            .big_unsigned()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserDict::Word).string_len(64).unique_key().not_null())
+                    .col(ColumnDef::new(UserDict::Freq).unsigned().null())
+                    .col(ColumnDef::new(UserDict::Tag).string_len(16).null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserDict::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum UserDict {
+    Table,
+    Id,
+    Word,
+    Freq,
+    Tag,
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Synonym::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Synonym::Id)
+                            .big_unsigned()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Synonym::Word).string_len(64).not_null())
+                    .col(ColumnDef::new(Synonym::Synonym).string_len(64).not_null())
+                    .index(
+                        Index::create()
+                            .name("idx-synonym-word-synonym")
+                            .col(Synonym::Word)
+                            .col(Synonym::Synonym)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Synonym::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Synonym {
+    Table,
+    Id,
+    Word,
+    Synonym,
+}
@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+use super::m20220101_000005_create_txt_version_table::TxtVersion;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 已有文档的指纹在启动时补齐，见simhash::backfill_simhash
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(ColumnDef::new(Txt::Simhash).big_unsigned().null())
+                    .to_owned(),
+            )
+            .await?;
+        // 恢复历史版本时需要其指纹
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TxtVersion::Table)
+                    .add_column(ColumnDef::new(TxtVersion::Simhash).big_unsigned().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TxtVersion::Table)
+                    .drop_column(TxtVersion::Simhash)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_column(Txt::Simhash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
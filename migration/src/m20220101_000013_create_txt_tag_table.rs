@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+use super::m20220101_000012_create_tag_table::Tag;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TxtTag::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(TxtTag::TxtId).big_unsigned().not_null())
+                    .col(ColumnDef::new(TxtTag::TagId).big_unsigned().not_null())
+                    .primary_key(
+                        Index::create()
+                            .name("pk-txt_tag")
+                            .col(TxtTag::TxtId)
+                            .col(TxtTag::TagId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_tag-txt-id")
+                        .from(TxtTag::Table, TxtTag::TxtId)
+                        .to(Txt::Table, Txt::Id)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_tag-tag-id")
+                        .from(TxtTag::Table, TxtTag::TagId)
+                        .to(Tag::Table, Tag::Id)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TxtTag::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TxtTag {
+    Table,
+    TxtId,
+    TagId,
+}
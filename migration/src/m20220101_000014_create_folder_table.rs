@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Folder::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Folder::Id)
+                            .big_unsigned()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Folder::Name).string_len(64).not_null())
+                    .col(ColumnDef::new(Folder::UserId).big_unsigned().not_null())
+                    // 为空时在顶层
+                    .col(ColumnDef::new(Folder::ParentId).big_unsigned().null())
+                    // 上传到文件夹中的文档的默认level
+                    .col(ColumnDef::new(Folder::Level).tiny_unsigned().not_null())
+                    .col(
+                        ColumnDef::new(Folder::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-folder-user-id")
+                        .from(Folder::Table, Folder::UserId)
+                        .to(User::Table, User::Id)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-folder-parent-id")
+                        .from(Folder::Table, Folder::ParentId)
+                        .to(Folder::Table, Folder::Id)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Folder::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Folder {
+    Table,
+    Id,
+    Name,
+    UserId,
+    ParentId,
+    Level,
+    CreatedAt,
+}
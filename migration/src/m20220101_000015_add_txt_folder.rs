@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+use super::m20220101_000014_create_folder_table::Folder;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 为空时在顶层
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(ColumnDef::new(Txt::FolderId).big_unsigned().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-txt-folder-id")
+                            .from_tbl(Txt::Table)
+                            .from_col(Txt::FolderId)
+                            .to_tbl(Folder::Table)
+                            .to_col(Folder::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_foreign_key(Alias::new("fk-txt-folder-id"))
+                    .drop_column(Txt::FolderId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
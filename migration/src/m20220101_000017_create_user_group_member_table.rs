@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000001_create_user_table::User;
+use super::m20220101_000016_create_user_group_table::UserGroup;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserGroupMember::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(UserGroupMember::GroupId).big_unsigned().not_null())
+                    .col(ColumnDef::new(UserGroupMember::UserId).big_unsigned().not_null())
+                    .primary_key(
+                        Index::create()
+                            .name("pk-user_group_member")
+                            .col(UserGroupMember::GroupId)
+                            .col(UserGroupMember::UserId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-user_group_member-group-id")
+                        .from(UserGroupMember::Table, UserGroupMember::GroupId)
+                        .to(UserGroup::Table, UserGroup::Id)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-user_group_member-user-id")
+                        .from(UserGroupMember::Table, UserGroupMember::UserId)
+                        .to(User::Table, User::Id)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserGroupMember::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum UserGroupMember {
+    Table,
+    GroupId,
+    UserId,
+}
@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000001_create_user_table::User;
+use super::m20220101_000002_create_txt_table::Txt;
+use super::m20220101_000016_create_user_group_table::UserGroup;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TxtGrant::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TxtGrant::Id)
+                            .big_unsigned()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TxtGrant::TxtId).big_unsigned().not_null())
+                    // 授权给用户或用户组，二者有且只有一个
+                    .col(ColumnDef::new(TxtGrant::UserId).big_unsigned().null())
+                    .col(ColumnDef::new(TxtGrant::GroupId).big_unsigned().null())
+                    // 为0时只读，为1时可以修改和删除
+                    .col(ColumnDef::new(TxtGrant::CanWrite).tiny_integer().not_null().default(0))
+                    // 为空时永不过期
+                    .col(ColumnDef::new(TxtGrant::ExpiresAt).timestamp().null())
+                    .col(
+                        ColumnDef::new(TxtGrant::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_grant-txt-id")
+                        .from(TxtGrant::Table, TxtGrant::TxtId)
+                        .to(Txt::Table, Txt::Id)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_grant-user-id")
+                        .from(TxtGrant::Table, TxtGrant::UserId)
+                        .to(User::Table, User::Id)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                        .name("fk-txt_grant-group-id")
+                        .from(TxtGrant::Table, TxtGrant::GroupId)
+                        .to(UserGroup::Table, UserGroup::Id)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TxtGrant::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TxtGrant {
+    Table,
+    Id,
+    TxtId,
+    UserId,
+    GroupId,
+    CanWrite,
+    ExpiresAt,
+    CreatedAt,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+use super::m20220101_000002_create_txt_table::Txt;
+
+// 指纹分成的段数和每段的位数，与simhash::SIMHASH_BANDS一致
+const BANDS: u32 = 4;
+const BAND_BITS: u32 = 16;
+
+fn band_column(band: u32) -> Alias {
+    Alias::new(format!("simhash_band{band}"))
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 指纹的各段，由数据库根据simhash生成，按段查找近似重复的候选
+        for band in 0..BANDS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Txt::Table)
+                        .add_column(ColumnDef::new(band_column(band)).small_unsigned().extra(
+                            format!(
+                                "GENERATED ALWAYS AS ((`simhash` >> {}) & 65535) STORED",
+                                band * BAND_BITS
+                            ),
+                        ))
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name(format!("idx-txt-simhash_band{band}"))
+                        .table(Txt::Table)
+                        .col(band_column(band))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        // 无法计算指纹的文档，启动时不再重试
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .add_column(
+                        ColumnDef::new(Txt::SimhashSkipped)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Txt::Table)
+                    .drop_column(Txt::SimhashSkipped)
+                    .to_owned(),
+            )
+            .await?;
+        for band in 0..BANDS {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .name(format!("idx-txt-simhash_band{band}"))
+                        .table(Txt::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Txt::Table)
+                        .drop_column(band_column(band))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::sync::{Arc, RwLock};
+
+use dotenv::dotenv;
+use jieba_rs::{Jieba, TokenizeMode};
+use lazy_static::lazy_static;
+use pinyin::ToPinyin;
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, Language, LowerCaser, NgramTokenizer, RawTokenizer, Stemmer, StopWordFilter,
+    TextAnalyzer, Token, TokenFilter, TokenStream, Tokenizer,
+};
+
+use crate::entities::user_dict;
+
+/// 分词相关的部署配置，改变后索引的schema随之改变，索引会被重建
+#[derive(Clone, Copy, Debug)]
+pub struct AnalyzerOptions {
+    // 繁体转为简体后再索引和查询
+    pub fold_traditional: bool,
+    // 拉丁字母词的词干提取语言，为None时不提取词干
+    pub stem_language: Option<Language>,
+}
+
+impl Default for AnalyzerOptions {
+    fn default() -> Self {
+        Self {
+            fold_traditional: true,
+            stem_language: Some(Language::English),
+        }
+    }
+}
+
+impl AnalyzerOptions {
+    /// 由环境变量FOLD_TRADITIONAL和STEM_LANGUAGE设置，
+    /// 默认繁简转换、按英语提取词干，STEM_LANGUAGE=none时不提取词干
+    pub fn from_env() -> Self {
+        dotenv().ok();
+        let default = Self::default();
+        let fold_traditional = env::var("FOLD_TRADITIONAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default.fold_traditional);
+        let stem_language = match env::var("STEM_LANGUAGE") {
+            Ok(lang) if lang.eq_ignore_ascii_case("none") => None,
+            Ok(lang) => parse_language(&lang).or(default.stem_language),
+            Err(_) => default.stem_language,
+        };
+        Self {
+            fold_traditional,
+            stem_language,
+        }
+    }
+
+    /// 标题和正文使用的分词器名，包含配置，配置改变时schema随之改变
+    pub fn tokenizer(&self) -> String {
+        let mut name = "jieba".to_string();
+        if self.fold_traditional {
+            name.push_str("_t2s");
+        }
+        if let Some(lang) = self.stem_language {
+            name.push('_');
+            name.push_str(&format!("{lang:?}").to_ascii_lowercase());
+        }
+        name
+    }
+
+    /// 标题和正文使用的分词器：jieba分词，繁简转换，
+    /// 拉丁字母词转小写、去掉变音符号、去停用词后提取词干
+    pub fn build_analyzer(&self, jieba: SharedJieba, stopwords: Vec<String>) -> TextAnalyzer {
+        let tokenizer = MixedTokenizer::new(jieba);
+        let mut builder = TextAnalyzer::builder(tokenizer).dynamic();
+        if self.fold_traditional {
+            builder = builder.filter_dynamic(T2SFilter);
+        }
+        builder = builder
+            .filter_dynamic(LowerCaser)
+            .filter_dynamic(AsciiFoldingFilter)
+            .filter_dynamic(StopWordFilter::remove(stopwords));
+        if let Some(lang) = self.stem_language {
+            builder = builder.filter_dynamic(Stemmer::new(lang));
+        }
+        builder.build()
+    }
+
+    /// 补全词使用的分词器：与标题和正文的相同，但不提取词干，补全出的是原词
+    pub fn build_suggest_analyzer(&self, jieba: SharedJieba, stopwords: Vec<String>) -> TextAnalyzer {
+        let options = Self {
+            stem_language: None,
+            ..*self
+        };
+        options.build_analyzer(jieba, stopwords)
+    }
+}
+
+/// 拼音域使用的分词器：连续的中日韩文字整段转为拼音，不用jieba分词也不去停用词，
+/// 每个字占一个位置，拼音的短语查询可以跨过词和停用词匹配
+pub fn build_pinyin_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(MixedTokenizer::new(RawTokenizer::default()))
+        .filter(LowerCaser)
+        .filter(PinyinFilter)
+        .build()
+}
+
+/// 标题前缀域最多索引的字数，更长的前缀只按前这么多字匹配
+pub const TITLE_PREFIX_MAX_CHARS: usize = 20;
+
+/// 标题前缀域使用的分词器：整个标题的各个前缀（edge ngram），繁简转换后转小写
+pub fn build_prefix_analyzer(fold_traditional: bool) -> TextAnalyzer {
+    let ngram = NgramTokenizer::prefix_only(1, TITLE_PREFIX_MAX_CHARS).unwrap();
+    let mut builder = TextAnalyzer::builder(ngram).dynamic();
+    if fold_traditional {
+        builder = builder.filter_dynamic(T2SFilter);
+    }
+    builder.filter_dynamic(LowerCaser).build()
+}
+
+lazy_static! {
+    // 只含默认词典的jieba，加载用户词典时以它为基础
+    static ref BASE_JIEBA: Jieba = Jieba::new();
+}
+
+/// 可在运行时替换用户词典的jieba分词器，各个分词器共享同一个词典
+#[derive(Clone)]
+pub struct SharedJieba {
+    jieba: Arc<RwLock<Jieba>>,
+}
+
+impl Default for SharedJieba {
+    fn default() -> Self {
+        Self {
+            jieba: Arc::new(RwLock::new(BASE_JIEBA.clone())),
+        }
+    }
+}
+
+impl SharedJieba {
+    /// 用默认词典加上用户词典替换当前词典，之后的分词立即生效
+    pub fn set_user_dict(&self, words: &[user_dict::Model]) {
+        let mut jieba = BASE_JIEBA.clone();
+        for word in words {
+            let freq = word.freq.map(|freq| freq as usize);
+            jieba.add_word(&word.word, freq, word.tag.as_deref());
+        }
+        *self.jieba.write().unwrap() = jieba;
+    }
+}
+
+impl Tokenizer for SharedJieba {
+    type TokenStream<'a> = MixedTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        let mut indices: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        indices.push(text.len());
+        let jieba = self.jieba.read().unwrap();
+        let tokens = jieba
+            .tokenize(text, TokenizeMode::Search, true)
+            .into_iter()
+            .map(|token| Token {
+                offset_from: indices[token.start],
+                offset_to: indices[token.end],
+                position: token.start,
+                text: token.word.to_string(),
+                position_length: token.end - token.start,
+            })
+            .collect();
+        MixedTokenStream { tokens, index: 0 }
+    }
+}
+
+/// 是否为中日韩文字，这些字符交给jieba分词
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x2E80..=0x2FDF       // 部首
+        | 0x3040..=0x30FF     // 平假名、片假名
+        | 0x3100..=0x31BF     // 注音
+        | 0x3400..=0x4DBF     // 扩展A
+        | 0x4E00..=0x9FFF     // 基本汉字
+        | 0xAC00..=0xD7AF     // 谚文
+        | 0xF900..=0xFAFF     // 兼容汉字
+        | 0x20000..=0x2FA1F   // 扩展B及以后
+    )
+}
+
+/// 中英混合分词器：连续的中日韩文字交给内部分词器（jieba），
+/// 其它连续的字母数字作为一个词，空白和标点丢弃
+///
+/// 词的位置按词数递增，与中间有多少空白和标点无关，短语查询才能匹配；
+/// jieba的搜索模式会输出重叠的词，同一段中日韩文字内保留jieba给出的相对位置，
+/// 这段文字占的位置数为其中的词覆盖到的最后位置
+#[derive(Clone)]
+pub struct MixedTokenizer<T> {
+    cjk: T,
+}
+
+impl<T: Tokenizer> MixedTokenizer<T> {
+    pub fn new(cjk: T) -> Self {
+        Self { cjk }
+    }
+}
+
+impl<T: Tokenizer> Tokenizer for MixedTokenizer<T> {
+    type TokenStream<'a> = MixedTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        let mut tokens = Vec::new();
+        // 当前连续片段的起点（字节偏移）和类型
+        let mut run: Option<(usize, bool)> = None;
+        // 下一个词的位置
+        let mut position = 0;
+        let chars = text.char_indices().chain([(text.len(), ' ')]);
+        for (offset, c) in chars {
+            let kind = if is_cjk(c) {
+                Some(true)
+            } else if c.is_alphanumeric() {
+                Some(false)
+            } else {
+                None
+            };
+            if let Some((start, cjk)) = run {
+                if kind == Some(cjk) {
+                    continue;
+                }
+                let segment = &text[start..offset];
+                if cjk {
+                    let mut next = position;
+                    let mut stream = self.cjk.token_stream(segment);
+                    while stream.advance() {
+                        let token = stream.token();
+                        next = next.max(position + token.position + token.position_length);
+                        tokens.push(Token {
+                            offset_from: start + token.offset_from,
+                            offset_to: start + token.offset_to,
+                            position: position + token.position,
+                            text: token.text.clone(),
+                            position_length: token.position_length,
+                        });
+                    }
+                    position = next;
+                } else {
+                    tokens.push(Token {
+                        offset_from: start,
+                        offset_to: offset,
+                        position,
+                        text: segment.to_string(),
+                        position_length: 1,
+                    });
+                    position += 1;
+                }
+            }
+            run = kind.map(|cjk| (offset, cjk));
+        }
+        MixedTokenStream { tokens, index: 0 }
+    }
+}
+
+pub struct MixedTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl TokenStream for MixedTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+/// 解析语言名，不区分大小写，如english、French
+fn parse_language(name: &str) -> Option<Language> {
+    let mut chars = name.trim().chars();
+    let first = chars.next()?.to_ascii_uppercase();
+    let name = format!("\"{first}{}\"", chars.as_str().to_ascii_lowercase());
+    serde_json::from_str(&name).ok()
+}
+
+/// 繁简转换过滤器，把繁体字转为简体字
+#[derive(Clone)]
+pub struct T2SFilter;
+
+impl TokenFilter for T2SFilter {
+    type Tokenizer<T: Tokenizer> = T2SFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> Self::Tokenizer<T> {
+        T2SFilterWrapper { tokenizer }
+    }
+}
+
+#[derive(Clone)]
+pub struct T2SFilterWrapper<T> {
+    tokenizer: T,
+}
+
+impl<T: Tokenizer> Tokenizer for T2SFilterWrapper<T> {
+    type TokenStream<'a> = T2STokenStream<T::TokenStream<'a>>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        T2STokenStream {
+            tail: self.tokenizer.token_stream(text),
+        }
+    }
+}
+
+pub struct T2STokenStream<T> {
+    tail: T,
+}
+
+impl<T: TokenStream> TokenStream for T2STokenStream<T> {
+    fn advance(&mut self) -> bool {
+        if !self.tail.advance() {
+            return false;
+        }
+        let token = self.tail.token_mut();
+        if !token.text.is_ascii() {
+            token.text = fast2s::convert(&token.text);
+        }
+        true
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+// 不带声调的拼音音节，ü写作v
+const SYLLABLES: &str = "a ai an ang ao \
+    ba bai ban bang bao bei ben beng bi bian biao bie bin bing bo bu \
+    ca cai can cang cao ce cen ceng cha chai chan chang chao che chen cheng chi chong chou chu \
+    chua chuai chuan chuang chui chun chuo ci cong cou cu cuan cui cun cuo \
+    da dai dan dang dao de dei den deng di dia dian diao die ding diu dong dou du duan dui dun duo \
+    e ei en eng er fa fan fang fei fen feng fo fou fu \
+    ga gai gan gang gao ge gei gen geng gong gou gu gua guai guan guang gui gun guo \
+    ha hai han hang hao he hei hen heng hong hou hu hua huai huan huang hui hun huo \
+    ji jia jian jiang jiao jie jin jing jiong jiu ju juan jue jun \
+    ka kai kan kang kao ke kei ken keng kong kou ku kua kuai kuan kuang kui kun kuo \
+    la lai lan lang lao le lei leng li lia lian liang liao lie lin ling liu lo long lou lu luan \
+    lun luo lv lve \
+    ma mai man mang mao me mei men meng mi mian miao mie min ming miu mo mou mu \
+    na nai nan nang nao ne nei nen neng ni nian niang niao nie nin ning niu nong nou nu nuan \
+    nun nuo nv nve o ou \
+    pa pai pan pang pao pei pen peng pi pian piao pie pin ping po pou pu \
+    qi qia qian qiang qiao qie qin qing qiong qiu qu quan que qun \
+    ran rang rao re ren reng ri rong rou ru rua ruan rui run ruo \
+    sa sai san sang sao se sen seng sha shai shan shang shao she shei shen sheng shi shou shu \
+    shua shuai shuan shuang shui shun shuo si song sou su suan sui sun suo \
+    ta tai tan tang tao te teng ti tian tiao tie ting tong tou tu tuan tui tun tuo \
+    wa wai wan wang wei wen weng wo wu xi xia xian xiang xiao xie xin xing xiong xiu xu xuan \
+    xue xun ya yan yang yao ye yi yin ying yo yong you yu yuan yue yun \
+    za zai zan zang zao ze zei zen zeng zha zhai zhan zhang zhao zhe zhei zhen zheng zhi zhong \
+    zhou zhu zhua zhuai zhuan zhuang zhui zhun zhuo zi zong zou zu zuan zui zun zuo";
+
+// 最长的音节，如zhuang
+const MAX_SYLLABLE_LEN: usize = 6;
+
+lazy_static! {
+    static ref SYLLABLE_SET: HashSet<&'static str> = SYLLABLES.split_whitespace().collect();
+}
+
+/// 把小写字母串切分为拼音音节，无法组成音节的字母单独成段（当作首字母）
+/// 优先让单独的字母最少，其次让段数最少，例如：luxien -> lu xi en，lxe -> l x e
+pub fn split_pinyin(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let n = chars.len();
+    let offset = |i: usize| if i < n { chars[i].0 } else { text.len() };
+    // best[i]为text[i..]的最优切分代价（单独字母数，段数）和下一段的结束位置
+    let mut best: Vec<((usize, usize), usize)> = vec![((0, 0), n); n + 1];
+    for i in (0..n).rev() {
+        let ((singles, segs), _) = best[i + 1];
+        let mut choice = ((singles + 1, segs + 1), i + 1);
+        for j in i + 1..=n.min(i + MAX_SYLLABLE_LEN) {
+            if SYLLABLE_SET.contains(&text[offset(i)..offset(j)]) {
+                let ((singles, segs), _) = best[j];
+                choice = choice.min(((singles, segs + 1), j));
+            }
+        }
+        best[i] = choice;
+    }
+
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < n {
+        let next = best[i].1;
+        res.push(&text[offset(i)..offset(next)]);
+        i = next;
+    }
+    res
+}
+
+/// 把词转为拼音音节，每个音节附带首字母（与音节相同时为None）
+/// 汉字按字转换，其它字符转为小写后切分音节
+pub fn to_pinyin(text: &str) -> Vec<(String, Option<String>)> {
+    let mut res = Vec::new();
+    let mut latin = String::new();
+    let flush = |latin: &mut String, res: &mut Vec<(String, Option<String>)>| {
+        res.extend(split_pinyin(latin).into_iter().map(|s| (s.to_string(), None)));
+        latin.clear();
+    };
+    for c in text.chars() {
+        match c.to_pinyin() {
+            Some(pinyin) => {
+                flush(&mut latin, &mut res);
+                let full = pinyin.plain().replace('ü', "v");
+                let initial = pinyin.first_letter().to_string();
+                let initial = (initial != full).then_some(initial);
+                res.push((full, initial));
+            }
+            None => latin.extend(c.to_lowercase()),
+        }
+    }
+    flush(&mut latin, &mut res);
+    res
+}
+
+/// 拼音过滤器，把词转为拼音音节，每个音节占一个位置，首字母与音节在同一位置
+/// 例如：路西恩 -> lu/l xi/x en/e，查询luxien或lxe时按短语匹配
+#[derive(Clone)]
+pub struct PinyinFilter;
+
+impl TokenFilter for PinyinFilter {
+    type Tokenizer<T: Tokenizer> = PinyinFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> Self::Tokenizer<T> {
+        PinyinFilterWrapper { tokenizer }
+    }
+}
+
+#[derive(Clone)]
+pub struct PinyinFilterWrapper<T> {
+    tokenizer: T,
+}
+
+impl<T: Tokenizer> Tokenizer for PinyinFilterWrapper<T> {
+    type TokenStream<'a> = PinyinTokenStream<T::TokenStream<'a>>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        PinyinTokenStream {
+            tail: self.tokenizer.token_stream(text),
+            token: Token::default(),
+            pending: VecDeque::new(),
+            position: 0,
+        }
+    }
+}
+
+pub struct PinyinTokenStream<T> {
+    tail: T,
+    token: Token,
+    // 待输出的音节
+    pending: VecDeque<Token>,
+    // 下一个音节的位置
+    position: usize,
+}
+
+impl<T: TokenStream> TokenStream for PinyinTokenStream<T> {
+    fn advance(&mut self) -> bool {
+        while self.pending.is_empty() {
+            if !self.tail.advance() {
+                return false;
+            }
+            let token = self.tail.token();
+            for (full, initial) in to_pinyin(&token.text) {
+                let syllable = Token {
+                    offset_from: token.offset_from,
+                    offset_to: token.offset_to,
+                    position: self.position,
+                    text: full,
+                    position_length: 1,
+                };
+                if let Some(initial) = initial {
+                    self.pending.push_back(Token {
+                        text: initial,
+                        ..syllable.clone()
+                    });
+                }
+                self.pending.push_back(syllable);
+                self.position += 1;
+            }
+        }
+        self.token = self.pending.pop_front().unwrap();
+        true
+    }
+
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.token
+    }
+}
@@ -0,0 +1,246 @@
+use std::borrow::Cow;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, BIG5, GB18030, GBK, UTF_8};
+use pulldown_cmark::{Event, Parser, TagEnd};
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::Reader;
+
+/// 支持的文档格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+    Plain,
+    Markdown,
+    Html,
+    Docx,
+    Pdf,
+}
+
+impl DocFormat {
+    /// 存入数据库的格式名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocFormat::Plain => "plain",
+            DocFormat::Markdown => "markdown",
+            DocFormat::Html => "html",
+            DocFormat::Docx => "docx",
+            DocFormat::Pdf => "pdf",
+        }
+    }
+
+    /// 是否为文本格式（需要识别编码）
+    pub fn is_text(&self) -> bool {
+        matches!(self, DocFormat::Plain | DocFormat::Markdown | DocFormat::Html)
+    }
+
+    /// 下载原文件时的Content-Type，不含charset
+    pub fn mime(&self) -> &'static str {
+        match self {
+            DocFormat::Plain => "text/plain",
+            DocFormat::Markdown => "text/markdown",
+            DocFormat::Html => "text/html",
+            DocFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            DocFormat::Pdf => "application/pdf",
+        }
+    }
+
+    /// 根据文件名和文件头判断格式，无法识别的当作纯文本
+    pub fn detect(filename: &str, data: &[u8]) -> Self {
+        if data.starts_with(b"%PDF-") {
+            return DocFormat::Pdf;
+        }
+        let ext = Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+        if data.starts_with(b"PK\x03\x04") && ext == "docx" {
+            return DocFormat::Docx;
+        }
+        match ext.as_str() {
+            "md" | "markdown" => DocFormat::Markdown,
+            "html" | "htm" => DocFormat::Html,
+            _ => {
+                let head = String::from_utf8_lossy(&data[..data.len().min(256)]);
+                let head = head.trim_start().to_ascii_lowercase();
+                if head.starts_with("<!doctype html") || head.starts_with("<html") {
+                    DocFormat::Html
+                } else {
+                    DocFormat::Plain
+                }
+            }
+        }
+    }
+}
+
+impl From<&str> for DocFormat {
+    fn from(value: &str) -> Self {
+        match value {
+            "markdown" => DocFormat::Markdown,
+            "html" => DocFormat::Html,
+            "docx" => DocFormat::Docx,
+            "pdf" => DocFormat::Pdf,
+            _ => DocFormat::Plain,
+        }
+    }
+}
+
+// 不是UTF-8时只尝试这些中文编码，单字节编码可以解码任意字节，无法区分二进制文件
+const LEGACY_ENCODINGS: [&Encoding; 3] = [GBK, GB18030, BIG5];
+
+/// 除制表、换行、换页、回车外的控制字符（含NUL），只会出现在二进制文件中
+fn has_control_bytes(data: &[u8]) -> bool {
+    data.iter()
+        .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\x0c' | b'\r'))
+}
+
+/// 识别文本的编码（UTF-8、GBK、GB18030、Big5）并转为UTF-8，
+/// 无法确定是这些编码的文本时返回错误
+pub fn decode_text(data: &[u8]) -> anyhow::Result<(String, &'static Encoding)> {
+    // 带BOM的直接按BOM解码
+    if let Some((encoding, bom_len)) = Encoding::for_bom(data) {
+        let text = encoding
+            .decode_without_bom_handling_and_without_replacement(&data[bom_len..])
+            .ok_or_else(|| anyhow::Error::msg("malformed text"))?;
+        return Ok((text.into_owned(), encoding));
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        return Ok((text.to_string(), UTF_8));
+    }
+    if has_control_bytes(data) {
+        return Err(anyhow::Error::msg("binary data"));
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    let (encoding, confident) = detector.guess_assess(None, false);
+    if !confident || !LEGACY_ENCODINGS.contains(&encoding) {
+        return Err(anyhow::Error::msg(format!("unsupported {} text", encoding.name())));
+    }
+    let text = encoding
+        .decode_without_bom_handling_and_without_replacement(data)
+        .ok_or_else(|| anyhow::Error::msg(format!("malformed {} text", encoding.name())))?;
+    Ok((text.into_owned(), encoding))
+}
+
+/// 从原文件中提取可检索的文本，文本格式的输入已转为UTF-8
+pub trait TextExtractor: Send + Sync {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String>;
+}
+
+pub struct PlainExtractor;
+pub struct MarkdownExtractor;
+pub struct HtmlExtractor;
+pub struct DocxExtractor;
+pub struct PdfExtractor;
+
+impl TextExtractor for PlainExtractor {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(String::from_utf8(data.to_vec())?)
+    }
+}
+
+impl TextExtractor for MarkdownExtractor {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String> {
+        let src = std::str::from_utf8(data)?;
+        let mut text = String::with_capacity(src.len());
+        for event in Parser::new(src) {
+            match event {
+                Event::Text(t) | Event::Code(t) => text.push_str(&t),
+                Event::SoftBreak | Event::HardBreak => text.push('\n'),
+                Event::End(
+                    TagEnd::Paragraph
+                    | TagEnd::Heading(_)
+                    | TagEnd::Item
+                    | TagEnd::CodeBlock
+                    | TagEnd::TableRow,
+                ) => text.push('\n'),
+                _ => (),
+            }
+        }
+        Ok(text)
+    }
+}
+
+impl TextExtractor for HtmlExtractor {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(html2text::config::plain_no_decorate().string_from_read(data, 1000)?)
+    }
+}
+
+impl TextExtractor for DocxExtractor {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+        let mut xml = String::new();
+        archive
+            .by_name("word/document.xml")?
+            .read_to_string(&mut xml)?;
+
+        // 只取w:t中的文字，每个段落w:p换行
+        let mut reader = Reader::from_str(&xml);
+        let mut text = String::with_capacity(xml.len() / 4);
+        let mut in_text = false;
+        loop {
+            match reader.read_event()? {
+                XmlEvent::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+                XmlEvent::End(e) if e.name().as_ref() == b"w:t" => in_text = false,
+                XmlEvent::End(e) if e.name().as_ref() == b"w:p" => text.push('\n'),
+                XmlEvent::Empty(e) if e.name().as_ref() == b"w:tab" => text.push('\t'),
+                XmlEvent::Empty(e) if e.name().as_ref() == b"w:br" => text.push('\n'),
+                XmlEvent::Text(t) if in_text => text.push_str(&t.unescape()?),
+                XmlEvent::Eof => break,
+                _ => (),
+            }
+        }
+        Ok(text)
+    }
+}
+
+impl TextExtractor for PdfExtractor {
+    fn extract(&self, data: &[u8]) -> anyhow::Result<String> {
+        // 损坏或加密的PDF可能让pdf-extract直接panic
+        std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data))
+            .map_err(|_| anyhow::Error::msg("pdf-extract panicked"))?
+            .map_err(anyhow::Error::from)
+    }
+}
+
+/// 获取某种格式对应的提取器
+pub fn get_extractor(format: DocFormat) -> &'static dyn TextExtractor {
+    match format {
+        DocFormat::Plain => &PlainExtractor,
+        DocFormat::Markdown => &MarkdownExtractor,
+        DocFormat::Html => &HtmlExtractor,
+        DocFormat::Docx => &DocxExtractor,
+        DocFormat::Pdf => &PdfExtractor,
+    }
+}
+
+/// 提取结果
+#[derive(Clone, Debug)]
+pub struct Extracted {
+    pub format: DocFormat,
+    // 文本格式原文件的编码，二进制格式为None
+    pub encoding: Option<&'static Encoding>,
+    pub text: String,
+}
+
+/// 识别格式和编码并提取文本
+pub fn extract_text(filename: &str, data: &[u8]) -> anyhow::Result<Extracted> {
+    let format = DocFormat::detect(filename, data);
+    let (data, encoding) = if format.is_text() {
+        let (text, encoding) = decode_text(data)?;
+        (Cow::Owned(text.into_bytes()), Some(encoding))
+    } else {
+        (Cow::Borrowed(data), None)
+    };
+    let text = get_extractor(format).extract(&data)?;
+    Ok(Extracted {
+        format,
+        encoding,
+        text,
+    })
+}
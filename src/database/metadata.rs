@@ -0,0 +1,63 @@
+use sea_orm::{prelude::DateTimeUtc, DatabaseConnection};
+use tokio::fs::metadata;
+
+use super::{
+    get_file_path,
+    mutation::{update_txt_metadata, update_version_size},
+    query::{get_txt_without_size, get_versions_by_txt_id, get_versions_without_size, read_file},
+};
+
+/// 原文件的大小和修改时间，文件按hash保存、写入后不再修改，修改时间即上传时间
+async fn file_info(hash: &str) -> std::io::Result<(u64, DateTimeUtc)> {
+    let metadata = metadata(get_file_path(hash)).await?;
+    Ok((metadata.len(), metadata.modified()?.into()))
+}
+
+/// 文件的大小和提取出的文本的字数
+async fn size_and_chars(hash: &str) -> std::io::Result<(u64, u64, DateTimeUtc)> {
+    let (size_bytes, modified) = file_info(hash).await?;
+    let char_count = read_file(hash.to_string()).await?.chars().count() as u64;
+    Ok((size_bytes, char_count, modified))
+}
+
+/// 为升级前上传的文档补上大小、字数和时间，返回补上的文档数
+///
+/// 更新时间为当前文件的上传时间，创建时间为它和各个历史版本中最早的上传时间；
+/// 补上的信息也在索引中，有文档被补上时需要重建索引
+pub async fn backfill_metadata(conn: &DatabaseConnection) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for doc in get_txt_without_size(conn).await? {
+        let (size_bytes, char_count, updated_at) = match size_and_chars(&doc.hash).await {
+            Ok(info) => info,
+            Err(e) => {
+                println!("-->> {:<12} -- skip doc {} {e:?}", "METADATA", doc.id);
+                continue;
+            }
+        };
+        let mut created_at = updated_at;
+        for version in get_versions_by_txt_id(conn, doc.id).await? {
+            if let Ok((_, modified)) = file_info(&version.hash).await {
+                created_at = created_at.min(modified);
+            }
+        }
+        update_txt_metadata(conn, doc.id, size_bytes, char_count, created_at, updated_at).await?;
+        count += 1;
+    }
+
+    // 恢复历史版本时使用其大小
+    let mut version_count = 0;
+    for version in get_versions_without_size(conn).await? {
+        match size_and_chars(&version.hash).await {
+            Ok((size_bytes, char_count, _)) => {
+                update_version_size(conn, version.id, size_bytes, char_count).await?;
+                version_count += 1;
+            }
+            Err(e) => println!("-->> {:<12} -- skip version {} {e:?}", "METADATA", version.id),
+        }
+    }
+    println!(
+        "-->> {:<12} -- backfill {count} docs, {version_count} versions",
+        "METADATA"
+    );
+    Ok(count)
+}
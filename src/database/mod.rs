@@ -1,10 +1,18 @@
 use tokio::fs::create_dir;
 use std::path::Path;
 
+pub mod analyzer;
 pub mod db;
+pub mod extract;
+pub mod metadata;
 pub mod query;
 pub mod mutation;
 pub mod search;
+pub mod simhash;
+pub mod stopword;
+pub mod synonym;
+pub mod trash;
+pub mod user_dict;
 
 const DATADIR: &str = "data";
 
@@ -27,4 +35,10 @@ pub async fn init_datadir() -> anyhow::Result<()> {
 #[inline]
 pub fn get_file_path(hash: &str) -> String {
     format!("{DATADIR}/{hash}")
+}
+
+/// 非纯文本文件提取出的UTF-8文本
+#[inline]
+pub fn get_text_path(hash: &str) -> String {
+    format!("{DATADIR}/{hash}.txt")
 }
\ No newline at end of file
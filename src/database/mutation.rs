@@ -1,40 +1,527 @@
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+
+use chrono::Utc;
 
 use sea_orm::{
-    sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, QueryFilter, Set
+    prelude::DateTimeUtc, sea_query::{Expr, OnConflict}, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, QueryFilter, Set
 };
 use tokio::{fs::remove_file, fs::File, io::AsyncWriteExt};
 
 use crate::entities::{prelude::*, *};
 
-use super::{get_file_path, query::get_txt_by_user_id};
+use super::{
+    get_file_path, get_text_path,
+    query::{get_folders_by_user_id, get_txt_by_user_id},
+};
+
+/// 文档某个版本的内容信息
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxtContent {
+    pub hash: String,
+    pub format: String,
+    pub encoding: Option<String>,
+    pub size_bytes: u64,
+    pub char_count: u64,
+    pub simhash: Option<u64>,
+}
+
+impl From<txt_version::Model> for TxtContent {
+    fn from(version: txt_version::Model) -> Self {
+        Self {
+            hash: version.hash,
+            format: version.format,
+            encoding: version.encoding,
+            size_bytes: version.size_bytes,
+            char_count: version.char_count,
+            simhash: version.simhash,
+        }
+    }
+}
 
 pub async fn add_txt_info(
     conn: &DatabaseConnection,
     title: &str,
-    hash: &str,
     user_id: &u64,
     level: &u8,
+    content: &TxtContent,
 ) -> Result<u64, DbErr> {
+    let now = Utc::now();
     let new_txt = txt::ActiveModel {
         title: ActiveValue::set(title.to_owned()),
-        hash: ActiveValue::set(hash.to_owned()),
+        hash: ActiveValue::set(content.hash.clone()),
         user_id: ActiveValue::set(user_id.to_owned()),
         level: ActiveValue::set(level.to_owned()),
+        format: ActiveValue::set(content.format.clone()),
+        encoding: ActiveValue::set(content.encoding.clone()),
+        created_at: ActiveValue::set(now),
+        updated_at: ActiveValue::set(now),
+        size_bytes: ActiveValue::set(content.size_bytes),
+        char_count: ActiveValue::set(content.char_count),
+        simhash: ActiveValue::set(content.simhash),
         ..Default::default()
     };
     let res = Txt::insert(new_txt).exec(conn).await?;
     Ok(res.last_insert_id)
 }
 
+/// 删除文档信息及其历史版本记录、标签、授权
 pub async fn delete_txt_info(
     conn: &DatabaseConnection,
     txt: txt::Model
 ) -> Result<(), DbErr> {
+    TxtGrant::delete_many()
+        .filter(txt_grant::Column::TxtId.eq(txt.id))
+        .exec(conn)
+        .await?;
+    TxtTag::delete_many()
+        .filter(txt_tag::Column::TxtId.eq(txt.id))
+        .exec(conn)
+        .await?;
+    TxtVersion::delete_many()
+        .filter(txt_version::Column::TxtId.eq(txt.id))
+        .exec(conn)
+        .await?;
     txt.delete(conn).await?;
     Ok(())
 }
 
+/// 将文档放入回收站
+pub async fn trash_txt(conn: &DatabaseConnection, doc: txt::Model) -> Result<txt::Model, DbErr> {
+    let mut doc: txt::ActiveModel = doc.into();
+    doc.deleted_at = Set(Some(Utc::now()));
+    doc.update(conn).await
+}
+
+/// 从回收站中恢复文档
+pub async fn restore_txt(conn: &DatabaseConnection, doc: txt::Model) -> Result<txt::Model, DbErr> {
+    let mut doc: txt::ActiveModel = doc.into();
+    doc.deleted_at = Set(None);
+    doc.update(conn).await
+}
+
+/// 将文档当前内容存为历史版本
+pub async fn add_txt_version(conn: &DatabaseConnection, txt: &txt::Model) -> Result<u64, DbErr> {
+    let new_version = txt_version::ActiveModel {
+        txt_id: ActiveValue::set(txt.id),
+        hash: ActiveValue::set(txt.hash.clone()),
+        format: ActiveValue::set(txt.format.clone()),
+        encoding: ActiveValue::set(txt.encoding.clone()),
+        size_bytes: ActiveValue::set(txt.size_bytes),
+        char_count: ActiveValue::set(txt.char_count),
+        simhash: ActiveValue::set(txt.simhash),
+        ..Default::default()
+    };
+    let res = TxtVersion::insert(new_version).exec(conn).await?;
+    Ok(res.last_insert_id)
+}
+
+pub async fn delete_txt_version(
+    conn: &DatabaseConnection,
+    version: txt_version::Model,
+) -> Result<(), DbErr> {
+    version.delete(conn).await?;
+    Ok(())
+}
+
+/// 给文档加上标签，不存在的标签自动创建，文档已有的标签忽略，返回新增的数量
+pub async fn add_txt_tags(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+    names: &[String],
+) -> Result<u64, DbErr> {
+    if names.is_empty() {
+        return Ok(0);
+    }
+    let new_tags = names.iter().map(|name| tag::ActiveModel {
+        name: ActiveValue::set(name.clone()),
+        ..Default::default()
+    });
+    Tag::insert_many(new_tags)
+        .on_conflict(
+            OnConflict::column(tag::Column::Name)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await?;
+
+    let tags = Tag::find()
+        .filter(tag::Column::Name.is_in(names.iter().cloned()))
+        .all(conn)
+        .await?;
+    let new_txt_tags = tags.iter().map(|tag| txt_tag::ActiveModel {
+        txt_id: ActiveValue::set(txt_id),
+        tag_id: ActiveValue::set(tag.id),
+    });
+    TxtTag::insert_many(new_txt_tags)
+        .on_conflict(
+            OnConflict::columns([txt_tag::Column::TxtId, txt_tag::Column::TagId])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await
+}
+
+/// 去掉文档的标签，返回删除的数量
+pub async fn remove_txt_tag(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+    name: &str,
+) -> Result<u64, DbErr> {
+    let Some(tag) = Tag::find()
+        .filter(tag::Column::Name.eq(name))
+        .one(conn)
+        .await?
+    else {
+        return Ok(0);
+    };
+    let res = TxtTag::delete_many()
+        .filter(txt_tag::Column::TxtId.eq(txt_id))
+        .filter(txt_tag::Column::TagId.eq(tag.id))
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+/// 新建文件夹，parent_id为None时在顶层
+pub async fn add_folder(
+    conn: &DatabaseConnection,
+    name: &str,
+    user_id: u64,
+    parent_id: Option<u64>,
+    level: u8,
+) -> Result<u64, DbErr> {
+    let new_folder = folder::ActiveModel {
+        name: ActiveValue::set(name.to_owned()),
+        user_id: ActiveValue::set(user_id),
+        parent_id: ActiveValue::set(parent_id),
+        level: ActiveValue::set(level),
+        created_at: ActiveValue::set(Utc::now()),
+        ..Default::default()
+    };
+    let res = Folder::insert(new_folder).exec(conn).await?;
+    Ok(res.last_insert_id)
+}
+
+/// 修改文件夹信息，parent_id为Some(None)时移到顶层
+pub async fn update_folder(
+    conn: &DatabaseConnection,
+    folder: folder::Model,
+    name: Option<String>,
+    parent_id: Option<Option<u64>>,
+    level: Option<u8>,
+) -> Result<folder::Model, DbErr> {
+    let mut folder: folder::ActiveModel = folder.into();
+    if let Some(name) = name {
+        folder.name = Set(name);
+    }
+    if let Some(parent_id) = parent_id {
+        folder.parent_id = Set(parent_id);
+    }
+    if let Some(level) = level {
+        folder.level = Set(level);
+    }
+    folder.update(conn).await
+}
+
+/// 删除文件夹，其中的文档（包括回收站中的）和子文件夹移到move_to，为None时移到顶层
+pub async fn delete_folder(
+    conn: &DatabaseConnection,
+    folder: folder::Model,
+    move_to: Option<u64>,
+) -> Result<(), DbErr> {
+    Txt::update_many()
+        .col_expr(txt::Column::FolderId, Expr::value(move_to))
+        .filter(txt::Column::FolderId.eq(folder.id))
+        .exec(conn)
+        .await?;
+    Folder::update_many()
+        .col_expr(folder::Column::ParentId, Expr::value(move_to))
+        .filter(folder::Column::ParentId.eq(folder.id))
+        .exec(conn)
+        .await?;
+    folder.delete(conn).await?;
+    Ok(())
+}
+
+/// 将文档移到文件夹中，folder_id为None时移到顶层
+pub async fn move_txt_to_folder(
+    conn: &DatabaseConnection,
+    doc: txt::Model,
+    folder_id: Option<u64>,
+) -> Result<txt::Model, DbErr> {
+    let mut doc: txt::ActiveModel = doc.into();
+    doc.folder_id = Set(folder_id);
+    doc.update(conn).await
+}
+
+/// 新建用户组
+pub async fn add_group(conn: &DatabaseConnection, name: &str) -> Result<u64, DbErr> {
+    let new_group = user_group::ActiveModel {
+        name: ActiveValue::set(name.to_owned()),
+        ..Default::default()
+    };
+    let res = UserGroup::insert(new_group).exec(conn).await?;
+    Ok(res.last_insert_id)
+}
+
+/// 删除用户组及其成员关系、授权给它的授权
+pub async fn delete_group(
+    conn: &DatabaseConnection,
+    group: user_group::Model,
+) -> Result<(), DbErr> {
+    TxtGrant::delete_many()
+        .filter(txt_grant::Column::GroupId.eq(group.id))
+        .exec(conn)
+        .await?;
+    UserGroupMember::delete_many()
+        .filter(user_group_member::Column::GroupId.eq(group.id))
+        .exec(conn)
+        .await?;
+    group.delete(conn).await?;
+    Ok(())
+}
+
+/// 把用户加入用户组，已在组中的忽略，返回新增的数量
+pub async fn add_group_member(
+    conn: &DatabaseConnection,
+    group_id: u64,
+    user_id: u64,
+) -> Result<u64, DbErr> {
+    let new_member = user_group_member::ActiveModel {
+        group_id: ActiveValue::set(group_id),
+        user_id: ActiveValue::set(user_id),
+    };
+    UserGroupMember::insert_many([new_member])
+        .on_conflict(
+            OnConflict::columns([
+                user_group_member::Column::GroupId,
+                user_group_member::Column::UserId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await
+}
+
+/// 把用户移出用户组，返回删除的数量
+pub async fn remove_group_member(
+    conn: &DatabaseConnection,
+    group_id: u64,
+    user_id: u64,
+) -> Result<u64, DbErr> {
+    let res = UserGroupMember::delete_many()
+        .filter(user_group_member::Column::GroupId.eq(group_id))
+        .filter(user_group_member::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+/// 文档的一条授权，授权给用户或用户组
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewGrant {
+    pub user_id: Option<u64>,
+    pub group_id: Option<u64>,
+    pub can_write: bool,
+    // 为None时永不过期
+    pub expires_at: Option<DateTimeUtc>,
+}
+
+/// 添加文档的授权
+pub async fn add_grant(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+    grant: &NewGrant,
+) -> Result<u64, DbErr> {
+    let new_grant = txt_grant::ActiveModel {
+        txt_id: ActiveValue::set(txt_id),
+        user_id: ActiveValue::set(grant.user_id),
+        group_id: ActiveValue::set(grant.group_id),
+        can_write: ActiveValue::set(grant.can_write as i8),
+        expires_at: ActiveValue::set(grant.expires_at),
+        created_at: ActiveValue::set(Utc::now()),
+        ..Default::default()
+    };
+    let res = TxtGrant::insert(new_grant).exec(conn).await?;
+    Ok(res.last_insert_id)
+}
+
+/// 删除授权，返回删除的数量
+pub async fn delete_grant(conn: &DatabaseConnection, id: u64) -> Result<u64, DbErr> {
+    let res = TxtGrant::delete_by_id(id).exec(conn).await?;
+    Ok(res.rows_affected)
+}
+
+/// 添加停用词，已存在的忽略，返回新增的数量
+pub async fn add_stopwords(conn: &DatabaseConnection, words: &[String]) -> Result<u64, DbErr> {
+    if words.is_empty() {
+        return Ok(0);
+    }
+    let new_words = words.iter().map(|word| stopword::ActiveModel {
+        word: ActiveValue::set(word.clone()),
+        ..Default::default()
+    });
+    Stopword::insert_many(new_words)
+        .on_conflict(
+            OnConflict::column(stopword::Column::Word)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await
+}
+
+/// 删除停用词，返回删除的数量
+pub async fn delete_stopword(conn: &DatabaseConnection, word: &str) -> Result<u64, DbErr> {
+    let res = Stopword::delete_many()
+        .filter(stopword::Column::Word.eq(word))
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+/// 添加用户词典中的词，已存在时更新词频和词性
+pub async fn save_user_word(
+    conn: &DatabaseConnection,
+    word: &str,
+    freq: Option<u32>,
+    tag: Option<String>,
+) -> Result<(), DbErr> {
+    let new_word = user_dict::ActiveModel {
+        word: ActiveValue::set(word.to_string()),
+        freq: ActiveValue::set(freq),
+        tag: ActiveValue::set(tag),
+        ..Default::default()
+    };
+    UserDict::insert(new_word)
+        .on_conflict(
+            OnConflict::column(user_dict::Column::Word)
+                .update_columns([user_dict::Column::Freq, user_dict::Column::Tag])
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await?;
+    Ok(())
+}
+
+/// 删除用户词典中的词，返回删除的数量
+pub async fn delete_user_word(conn: &DatabaseConnection, word: &str) -> Result<u64, DbErr> {
+    let res = UserDict::delete_many()
+        .filter(user_dict::Column::Word.eq(word))
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+/// 添加同义词，pairs为（词，同义词），已存在的忽略，返回新增的数量
+pub async fn add_synonyms(
+    conn: &DatabaseConnection,
+    pairs: &[(String, String)],
+) -> Result<u64, DbErr> {
+    if pairs.is_empty() {
+        return Ok(0);
+    }
+    let new_pairs = pairs.iter().map(|(word, synonym)| synonym::ActiveModel {
+        word: ActiveValue::set(word.clone()),
+        synonym: ActiveValue::set(synonym.clone()),
+        ..Default::default()
+    });
+    Synonym::insert_many(new_pairs)
+        .on_conflict(
+            OnConflict::columns([synonym::Column::Word, synonym::Column::Synonym])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await
+}
+
+/// 删除同义词，返回删除的数量
+pub async fn delete_synonym(conn: &DatabaseConnection, id: u64) -> Result<u64, DbErr> {
+    let res = Synonym::delete_by_id(id).exec(conn).await?;
+    Ok(res.rows_affected)
+}
+
+/// 修改文档的当前内容
+pub async fn update_doc_content(
+    conn: &DatabaseConnection,
+    doc: txt::Model,
+    content: &TxtContent,
+) -> Result<txt::Model, DbErr> {
+    let mut doc: txt::ActiveModel = doc.into();
+    doc.hash = Set(content.hash.clone());
+    doc.format = Set(content.format.clone());
+    doc.encoding = Set(content.encoding.clone());
+    doc.size_bytes = Set(content.size_bytes);
+    doc.char_count = Set(content.char_count);
+    doc.simhash = Set(content.simhash);
+    doc.updated_at = Set(Utc::now());
+    doc.update(conn).await
+}
+
+/// 只更新文档的指纹，不改变更新时间
+pub async fn update_txt_simhash(
+    conn: &DatabaseConnection,
+    id: u64,
+    simhash: u64,
+) -> Result<(), DbErr> {
+    Txt::update_many()
+        .col_expr(txt::Column::Simhash, Expr::value(simhash))
+        .filter(txt::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// 记录无法计算指纹的文档，之后不再重试
+pub async fn skip_txt_simhash(conn: &DatabaseConnection, id: u64) -> Result<(), DbErr> {
+    Txt::update_many()
+        .col_expr(txt::Column::SimhashSkipped, Expr::value(1))
+        .filter(txt::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// 补上文档的大小、字数和时间，用于升级前上传的文档
+pub async fn update_txt_metadata(
+    conn: &DatabaseConnection,
+    id: u64,
+    size_bytes: u64,
+    char_count: u64,
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
+) -> Result<(), DbErr> {
+    Txt::update_many()
+        .col_expr(txt::Column::SizeBytes, Expr::value(size_bytes))
+        .col_expr(txt::Column::CharCount, Expr::value(char_count))
+        .col_expr(txt::Column::CreatedAt, Expr::value(created_at))
+        .col_expr(txt::Column::UpdatedAt, Expr::value(updated_at))
+        .filter(txt::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// 补上历史版本的大小和字数
+pub async fn update_version_size(
+    conn: &DatabaseConnection,
+    id: u64,
+    size_bytes: u64,
+    char_count: u64,
+) -> Result<(), DbErr> {
+    TxtVersion::update_many()
+        .col_expr(txt_version::Column::SizeBytes, Expr::value(size_bytes))
+        .col_expr(txt_version::Column::CharCount, Expr::value(char_count))
+        .filter(txt_version::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
 pub async fn add_user(
     conn: &DatabaseConnection,
     username: &str,
@@ -56,13 +543,27 @@ pub async fn add_user(
 
 pub async fn write_file(hash: &str, data: &[u8]) -> Result<(), Error> {
     let mut f = File::create(get_file_path(hash)).await?;
-    let _ = f.write_all(data).await?;
-    _ = f.flush();
+    f.write_all(data).await?;
+    f.flush().await?;
     Ok(())
 }
 
+/// 写入从原文件提取出的文本
+pub async fn write_text_file(hash: &str, text: &str) -> Result<(), Error> {
+    let mut f = File::create(get_text_path(hash)).await?;
+    f.write_all(text.as_bytes()).await?;
+    f.flush().await?;
+    Ok(())
+}
+
+/// 删除原文件及提取出的文本，已经不存在的文件视为已删除
 pub async fn delete_file(hash: &str) -> Result<(), Error> {
-    remove_file(get_file_path(hash)).await?;
+    for path in [get_file_path(hash), get_text_path(hash)] {
+        match remove_file(path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
     Ok(())
 }
 
@@ -79,8 +580,9 @@ pub async fn update_doc_info(
     if let Some(level) = level {
         doc.level = Set(level);
     }
+    doc.updated_at = Set(Utc::now());
 
-    Ok(doc.update(conn).await?)
+    doc.update(conn).await
 }
 
 pub async fn update_user_info(
@@ -120,6 +622,25 @@ async fn move_onwer(
         .filter(txt::Column::UserId.eq(from.id))
         .exec(conn)
         .await?;
+    // 文件夹随文档一起转移
+    let _ = Folder::update_many()
+        .col_expr(folder::Column::UserId, Expr::value(to.id))
+        .filter(folder::Column::UserId.eq(from.id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// 删除用户的成员关系和授权给该用户的授权
+async fn remove_user_grants(conn: &DatabaseConnection, user: &user::Model) -> Result<(), DbErr> {
+    TxtGrant::delete_many()
+        .filter(txt_grant::Column::UserId.eq(user.id))
+        .exec(conn)
+        .await?;
+    UserGroupMember::delete_many()
+        .filter(user_group_member::Column::UserId.eq(user.id))
+        .exec(conn)
+        .await?;
     Ok(())
 }
 
@@ -130,14 +651,17 @@ pub async fn delete_user(
     move_to: Option<user::Model>,
 ) -> Result<(), DbErr> {
     let docs = get_txt_by_user_id(conn, user.id).await?;
-    // 没有文档直接删除
-    if docs.is_empty() {
+    let folders = get_folders_by_user_id(conn, user.id).await?;
+    // 没有文档和文件夹直接删除
+    if docs.is_empty() && folders.is_empty() {
+        remove_user_grants(conn, &user).await?;
         user.delete(conn).await?;
         Ok(())
-    // 有文档，提供move_to，尝试转移文档所有权，再删除
+    // 有文档或文件夹，提供move_to，尝试转移所有权，再删除
     } else if move_to.is_some() {
         let move_to = move_to.unwrap();
         move_onwer(conn, user.clone(), move_to).await?;
+        remove_user_grants(conn, &user).await?;
         user.delete(conn).await?;
         Ok(())
     } else {
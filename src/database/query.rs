@@ -1,9 +1,11 @@
 use super::{
     super::entities::{prelude::*, *},
-    get_file_path,
+    get_file_path, get_text_path,
+    search::{Sort, SortBy, SortOrder},
 };
-use sea_orm::*;
-use std::io::Error;
+use sea_orm::{prelude::DateTimeUtc, *};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
 use tokio::{fs::File, io::AsyncReadExt};
 
 pub async fn get_user_by_id(
@@ -27,13 +29,34 @@ pub async fn get_all_users(conn: &DatabaseConnection) -> Result<Vec<user::Model>
     User::find().all(conn).await
 }
 
+// 以下txt查询若无特别说明，均不包含回收站中的文档
+
 pub async fn get_txt_by_id(
     conn: &DatabaseConnection,
     id: u64,
 ) -> Result<Option<txt::Model>, DbErr> {
-    Txt::find_by_id(id).one(conn).await
+    Txt::find_by_id(id)
+        .filter(txt::Column::DeletedAt.is_null())
+        .one(conn)
+        .await
 }
 
+/// 一次查询多个txt，返回顺序不保证与ids一致
+pub async fn get_txt_by_ids(
+    conn: &DatabaseConnection,
+    ids: &[u64],
+) -> Result<Vec<txt::Model>, DbErr> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    Txt::find()
+        .filter(txt::Column::Id.is_in(ids.iter().copied()))
+        .filter(txt::Column::DeletedAt.is_null())
+        .all(conn)
+        .await
+}
+
+/// 用户的所有文档，包含回收站中的文档
 pub async fn get_txt_by_user_id(
     conn: &DatabaseConnection,
     user_id: u64,
@@ -50,20 +73,527 @@ pub async fn get_txt_by_hash(
 ) -> Result<Option<txt::Model>, DbErr> {
     Txt::find()
         .filter(txt::Column::Hash.eq(hash))
+        .filter(txt::Column::DeletedAt.is_null())
+        .one(conn)
+        .await
+}
+
+/// hash是否已被某个文档（包括回收站中的）或历史版本使用
+pub async fn hash_exists(conn: &DatabaseConnection, hash: &str) -> Result<bool, DbErr> {
+    let txt = Txt::find()
+        .filter(txt::Column::Hash.eq(hash))
+        .one(conn)
+        .await?;
+    if txt.is_some() {
+        return Ok(true);
+    }
+    Ok(get_version_by_hash(conn, hash).await?.is_some())
+}
+
+/// 回收站中的某个文档
+pub async fn get_trashed_txt_by_id(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Option<txt::Model>, DbErr> {
+    Txt::find_by_id(id)
+        .filter(txt::Column::DeletedAt.is_not_null())
+        .one(conn)
+        .await
+}
+
+/// 回收站中的文档，user_id为None时返回所有用户的
+pub async fn get_trashed_txt(
+    conn: &DatabaseConnection,
+    user_id: Option<u64>,
+) -> Result<Vec<txt::Model>, DbErr> {
+    let mut select = Txt::find().filter(txt::Column::DeletedAt.is_not_null());
+    if let Some(user_id) = user_id {
+        select = select.filter(txt::Column::UserId.eq(user_id));
+    }
+    select
+        .order_by_desc(txt::Column::DeletedAt)
+        .all(conn)
+        .await
+}
+
+/// 在before之前放入回收站的文档
+pub async fn get_trashed_txt_before(
+    conn: &DatabaseConnection,
+    before: DateTimeUtc,
+) -> Result<Vec<txt::Model>, DbErr> {
+    Txt::find()
+        .filter(txt::Column::DeletedAt.lt(before))
+        .all(conn)
+        .await
+}
+
+/// 所有停用词
+pub async fn get_all_stopwords(conn: &DatabaseConnection) -> Result<Vec<String>, DbErr> {
+    Stopword::find()
+        .select_only()
+        .column(stopword::Column::Word)
+        .order_by_asc(stopword::Column::Word)
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+pub async fn count_stopwords(conn: &DatabaseConnection) -> Result<u64, DbErr> {
+    Stopword::find().count(conn).await
+}
+
+/// 用户词典中的所有词
+pub async fn get_all_user_words(conn: &DatabaseConnection) -> Result<Vec<user_dict::Model>, DbErr> {
+    UserDict::find()
+        .order_by_asc(user_dict::Column::Word)
+        .all(conn)
+        .await
+}
+
+/// 所有同义词
+pub async fn get_all_synonyms(conn: &DatabaseConnection) -> Result<Vec<synonym::Model>, DbErr> {
+    Synonym::find()
+        .order_by_asc(synonym::Column::Word)
+        .order_by_asc(synonym::Column::Synonym)
+        .all(conn)
+        .await
+}
+
+/// 所有标签，按名称排序
+pub async fn get_all_tags(conn: &DatabaseConnection) -> Result<Vec<tag::Model>, DbErr> {
+    Tag::find().order_by_asc(tag::Column::Name).all(conn).await
+}
+
+/// 文档的标签名，按名称排序
+pub async fn get_tags_by_txt_id(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+) -> Result<Vec<String>, DbErr> {
+    Tag::find()
+        .select_only()
+        .column(tag::Column::Name)
+        .inner_join(TxtTag)
+        .filter(txt_tag::Column::TxtId.eq(txt_id))
+        .order_by_asc(tag::Column::Name)
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// 所有文档的标签名，用于重建索引
+pub async fn get_all_txt_tags(
+    conn: &DatabaseConnection,
+) -> Result<HashMap<u64, Vec<String>>, DbErr> {
+    let pairs: Vec<(u64, String)> = TxtTag::find()
+        .select_only()
+        .column(txt_tag::Column::TxtId)
+        .column(tag::Column::Name)
+        .inner_join(Tag)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let mut res: HashMap<u64, Vec<String>> = HashMap::new();
+    for (txt_id, name) in pairs {
+        res.entry(txt_id).or_default().push(name);
+    }
+    Ok(res)
+}
+
+pub async fn get_folder_by_id(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Option<folder::Model>, DbErr> {
+    Folder::find_by_id(id).one(conn).await
+}
+
+/// 用户在文件夹下的子文件夹，parent_id为None时为用户顶层的文件夹
+pub async fn get_folders_by_parent(
+    conn: &DatabaseConnection,
+    parent_id: Option<u64>,
+    user_id: u64,
+) -> Result<Vec<folder::Model>, DbErr> {
+    let parent = match parent_id {
+        Some(id) => folder::Column::ParentId.eq(id),
+        None => folder::Column::ParentId.is_null(),
+    };
+    Folder::find()
+        .filter(parent)
+        .filter(folder::Column::UserId.eq(user_id))
+        .order_by_asc(folder::Column::Name)
+        .all(conn)
+        .await
+}
+
+pub async fn get_folders_by_user_id(
+    conn: &DatabaseConnection,
+    user_id: u64,
+) -> Result<Vec<folder::Model>, DbErr> {
+    Folder::find()
+        .filter(folder::Column::UserId.eq(user_id))
+        .all(conn)
+        .await
+}
+
+/// 所有文件夹的父文件夹
+async fn get_folder_parents(
+    conn: &DatabaseConnection,
+) -> Result<HashMap<u64, Option<u64>>, DbErr> {
+    let pairs: Vec<(u64, Option<u64>)> = Folder::find()
+        .select_only()
+        .column(folder::Column::Id)
+        .column(folder::Column::ParentId)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(pairs.into_iter().collect())
+}
+
+/// 从顶层到id的文件夹id路径，包括id自身
+fn folder_path(parents: &HashMap<u64, Option<u64>>, id: u64) -> Vec<u64> {
+    let mut path = vec![id];
+    let mut current = id;
+    while let Some(Some(parent)) = parents.get(&current) {
+        // 数据库中的环不应出现，防止死循环
+        if path.contains(parent) {
+            break;
+        }
+        path.push(*parent);
+        current = *parent;
+    }
+    path.reverse();
+    path
+}
+
+/// 文件夹的路径，从顶层的文件夹开始，包括其自身
+pub async fn get_folder_path(conn: &DatabaseConnection, id: u64) -> Result<Vec<u64>, DbErr> {
+    let parents = get_folder_parents(conn).await?;
+    Ok(folder_path(&parents, id))
+}
+
+/// 所有文件夹的路径，用于重建索引
+pub async fn get_all_folder_paths(
+    conn: &DatabaseConnection,
+) -> Result<HashMap<u64, Vec<u64>>, DbErr> {
+    let parents = get_folder_parents(conn).await?;
+    Ok(parents
+        .keys()
+        .map(|id| (*id, folder_path(&parents, *id)))
+        .collect())
+}
+
+/// 文件夹及其下所有层级的子文件夹的id
+pub async fn get_folder_subtree_ids(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Vec<u64>, DbErr> {
+    let parents = get_folder_parents(conn).await?;
+    Ok(parents
+        .keys()
+        .filter(|other| folder_path(&parents, **other).contains(&id))
+        .copied()
+        .collect())
+}
+
+/// 用户文件夹中level不超过查看者level的文档和granted中的文档，
+/// folder_id为None时为用户不在任何文件夹中的文档
+pub async fn get_txt_by_folder_id(
+    conn: &DatabaseConnection,
+    folder_id: Option<u64>,
+    user_id: u64,
+    level: u8,
+    granted: &[u64],
+) -> Result<Vec<txt::Model>, DbErr> {
+    let folder = match folder_id {
+        Some(id) => txt::Column::FolderId.eq(id),
+        None => txt::Column::FolderId.is_null(),
+    };
+    Txt::find()
+        .filter(folder)
+        .filter(txt::Column::UserId.eq(user_id))
+        .filter(visible_condition(level, granted))
+        .filter(txt::Column::DeletedAt.is_null())
+        .order_by_asc(txt::Column::Title)
+        .all(conn)
+        .await
+}
+
+/// 在这些文件夹中的文档
+pub async fn get_txt_in_folders(
+    conn: &DatabaseConnection,
+    folder_ids: &[u64],
+) -> Result<Vec<txt::Model>, DbErr> {
+    if folder_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    Txt::find()
+        .filter(txt::Column::FolderId.is_in(folder_ids.iter().copied()))
+        .filter(txt::Column::DeletedAt.is_null())
+        .all(conn)
+        .await
+}
+
+/// 文件夹是否为空，回收站中的文档也算在内
+pub async fn folder_is_empty(conn: &DatabaseConnection, id: u64) -> Result<bool, DbErr> {
+    let docs = Txt::find()
+        .filter(txt::Column::FolderId.eq(id))
+        .count(conn)
+        .await?;
+    let folders = Folder::find()
+        .filter(folder::Column::ParentId.eq(id))
+        .count(conn)
+        .await?;
+    Ok(docs == 0 && folders == 0)
+}
+
+/// level不超过用户level，或者在granted中
+fn visible_condition(level: u8, granted: &[u64]) -> Condition {
+    Condition::any()
+        .add(txt::Column::Level.lte(level))
+        .add(txt::Column::Id.is_in(granted.iter().copied()))
+}
+
+pub async fn get_all_groups(conn: &DatabaseConnection) -> Result<Vec<user_group::Model>, DbErr> {
+    UserGroup::find()
+        .order_by_asc(user_group::Column::Name)
+        .all(conn)
+        .await
+}
+
+pub async fn get_group_by_id(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Option<user_group::Model>, DbErr> {
+    UserGroup::find_by_id(id).one(conn).await
+}
+
+/// 用户组的成员id
+pub async fn get_group_members(
+    conn: &DatabaseConnection,
+    group_id: u64,
+) -> Result<Vec<u64>, DbErr> {
+    UserGroupMember::find()
+        .select_only()
+        .column(user_group_member::Column::UserId)
+        .filter(user_group_member::Column::GroupId.eq(group_id))
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// 用户所在的用户组id
+pub async fn get_group_ids_by_user_id(
+    conn: &DatabaseConnection,
+    user_id: u64,
+) -> Result<Vec<u64>, DbErr> {
+    UserGroupMember::find()
+        .select_only()
+        .column(user_group_member::Column::GroupId)
+        .filter(user_group_member::Column::UserId.eq(user_id))
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// 文档的所有授权，包括已过期的
+pub async fn get_grants_by_txt_id(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+) -> Result<Vec<txt_grant::Model>, DbErr> {
+    TxtGrant::find()
+        .filter(txt_grant::Column::TxtId.eq(txt_id))
+        .all(conn)
+        .await
+}
+
+pub async fn get_grant_by_id(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Option<txt_grant::Model>, DbErr> {
+    TxtGrant::find_by_id(id).one(conn).await
+}
+
+/// 授权给用户或其所在用户组、未过期的授权，write为true时只包括可以修改的
+async fn active_grants(
+    conn: &DatabaseConnection,
+    user_id: u64,
+    write: bool,
+) -> Result<Select<TxtGrant>, DbErr> {
+    let groups = get_group_ids_by_user_id(conn, user_id).await?;
+    let mut select = TxtGrant::find()
+        .filter(
+            Condition::any()
+                .add(txt_grant::Column::UserId.eq(user_id))
+                .add(txt_grant::Column::GroupId.is_in(groups)),
+        )
+        .filter(
+            Condition::any()
+                .add(txt_grant::Column::ExpiresAt.is_null())
+                .add(txt_grant::Column::ExpiresAt.gt(chrono::Utc::now())),
+        );
+    if write {
+        select = select.filter(txt_grant::Column::CanWrite.eq(1));
+    }
+    Ok(select)
+}
+
+/// 用户通过授权可以访问的文档id
+pub async fn get_granted_txt_ids(
+    conn: &DatabaseConnection,
+    user_id: u64,
+    write: bool,
+) -> Result<Vec<u64>, DbErr> {
+    active_grants(conn, user_id, write)
+        .await?
+        .select_only()
+        .column(txt_grant::Column::TxtId)
+        .distinct()
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// 用户是否有文档的授权
+pub async fn has_grant(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+    user_id: u64,
+    write: bool,
+) -> Result<bool, DbErr> {
+    let count = active_grants(conn, user_id, write)
+        .await?
+        .filter(txt_grant::Column::TxtId.eq(txt_id))
+        .count(conn)
+        .await?;
+    Ok(count > 0)
+}
+
+/// 文档的所有历史版本，新的在前
+pub async fn get_versions_by_txt_id(
+    conn: &DatabaseConnection,
+    txt_id: u64,
+) -> Result<Vec<txt_version::Model>, DbErr> {
+    TxtVersion::find()
+        .filter(txt_version::Column::TxtId.eq(txt_id))
+        .order_by_desc(txt_version::Column::Id)
+        .all(conn)
+        .await
+}
+
+pub async fn get_version_by_id(
+    conn: &DatabaseConnection,
+    id: u64,
+) -> Result<Option<txt_version::Model>, DbErr> {
+    TxtVersion::find_by_id(id).one(conn).await
+}
+
+pub async fn get_version_by_hash(
+    conn: &DatabaseConnection,
+    hash: &str,
+) -> Result<Option<txt_version::Model>, DbErr> {
+    TxtVersion::find()
+        .filter(txt_version::Column::Hash.eq(hash))
         .one(conn)
         .await
 }
 
 pub async fn get_all_txt(conn: &DatabaseConnection) -> Result<Vec<txt::Model>, DbErr> {
-    Txt::find().all(conn).await
+    Txt::find()
+        .filter(txt::Column::DeletedAt.is_null())
+        .all(conn)
+        .await
+}
+
+/// 所有有指纹的文档的（id，指纹），不含回收站中的
+pub async fn get_all_simhashes(conn: &DatabaseConnection) -> Result<Vec<(u64, u64)>, DbErr> {
+    Txt::find()
+        .select_only()
+        .column(txt::Column::Id)
+        .column(txt::Column::Simhash)
+        .filter(txt::Column::Simhash.is_not_null())
+        .filter(txt::Column::DeletedAt.is_null())
+        .order_by_asc(txt::Column::Id)
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// 指纹至少有一段与bands相同的文档的（id，指纹），不含回收站中的
+///
+/// 各段由数据库生成，见迁移m20220101_000019
+pub async fn get_simhashes_by_bands(
+    conn: &DatabaseConnection,
+    bands: &[u16],
+) -> Result<Vec<(u64, u64)>, DbErr> {
+    let mut condition = Condition::any();
+    for (band, value) in bands.iter().enumerate() {
+        let column = sea_query::Alias::new(format!("simhash_band{band}"));
+        condition = condition.add(sea_query::Expr::col(column).eq(*value));
+    }
+    Txt::find()
+        .select_only()
+        .column(txt::Column::Id)
+        .column(txt::Column::Simhash)
+        .filter(condition)
+        .filter(txt::Column::DeletedAt.is_null())
+        .order_by_asc(txt::Column::Id)
+        .into_tuple()
+        .all(conn)
+        .await
 }
 
+/// 还没有指纹、也没有被跳过的文档，包括回收站中的
+pub async fn get_txt_without_simhash(conn: &DatabaseConnection) -> Result<Vec<txt::Model>, DbErr> {
+    Txt::find()
+        .filter(txt::Column::Simhash.is_null())
+        .filter(txt::Column::SimhashSkipped.eq(0))
+        .all(conn)
+        .await
+}
+
+/// 升级前上传、还没有大小等信息的文档，包括回收站中的
+///
+/// 上传时会拒绝空文件，大小为0即没有记录
+pub async fn get_txt_without_size(conn: &DatabaseConnection) -> Result<Vec<txt::Model>, DbErr> {
+    Txt::find()
+        .filter(txt::Column::SizeBytes.eq(0))
+        .all(conn)
+        .await
+}
+
+/// 升级前保存、还没有大小的历史版本
+pub async fn get_versions_without_size(
+    conn: &DatabaseConnection,
+) -> Result<Vec<txt_version::Model>, DbErr> {
+    TxtVersion::find()
+        .filter(txt_version::Column::SizeBytes.eq(0))
+        .all(conn)
+        .await
+}
+
+/// level不超过用户level的文档和granted中的文档，按sort排序，按相关度排序时按id
 pub async fn get_all_txt_lte_level(
     conn: &DatabaseConnection,
     level: u8,
+    granted: &[u64],
+    sort: Sort,
 ) -> Result<Vec<txt::Model>, DbErr> {
+    let column = match sort.by {
+        SortBy::Relevance => txt::Column::Id,
+        SortBy::Title => txt::Column::Title,
+        SortBy::Level => txt::Column::Level,
+        SortBy::Owner => txt::Column::UserId,
+        SortBy::Created => txt::Column::CreatedAt,
+        SortBy::Size => txt::Column::SizeBytes,
+    };
+    let order = match sort.order {
+        SortOrder::Asc => Order::Asc,
+        SortOrder::Desc => Order::Desc,
+    };
     Txt::find()
-        .filter(txt::Column::Level.lte(level))
+        .filter(visible_condition(level, granted))
+        .filter(txt::Column::DeletedAt.is_null())
+        .order_by(column, order)
+        .order_by_asc(txt::Column::Id)
         .all(conn)
         .await
 }
@@ -84,20 +614,16 @@ pub async fn get_txt_maxlevel_by_userid(
         .into_model::<SelectResult>()
         .one(conn)
         .await?;
-    let res = if let Some(res) = res {
-        if let Some(v) = res.max_level {
-            Some(v)
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-    Ok(res)
+    Ok(res.and_then(|res| res.max_level))
 }
 
+/// 读取文档的文本，优先读取提取出的文本，没有则读取原文件
 pub async fn read_file(hash: String) -> Result<String, Error> {
-    let mut f = match File::open(get_file_path(&hash)).await {
+    let f = match File::open(get_text_path(&hash)).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => File::open(get_file_path(&hash)).await,
+        f => f,
+    };
+    let mut f = match f {
         Ok(f) => f,
         Err(e) => {
             println!("-->>{:<12} --- {hash:?} may not exsist!!!", "READ_FROM_FS");
@@ -105,12 +631,17 @@ pub async fn read_file(hash: String) -> Result<String, Error> {
         }
     };
     let mut buf = String::with_capacity(15000);
-    let _ = match f.read_to_string(&mut buf).await {
-        Ok(_) => (),
-        Err(e) => {
-            println!("-->>{:<12} --- {hash:?} not UTF-8", "READ_FROM_FS");
-            return Err(e);
-        }
-    };
+    if let Err(e) = f.read_to_string(&mut buf).await {
+        println!("-->>{:<12} --- {hash:?} not UTF-8", "READ_FROM_FS");
+        return Err(e);
+    }
+    Ok(buf)
+}
+
+/// 读取原文件
+pub async fn read_raw_file(hash: &str) -> Result<Vec<u8>, Error> {
+    let mut f = File::open(get_file_path(hash)).await?;
+    let mut buf = Vec::with_capacity(15000);
+    f.read_to_end(&mut buf).await?;
     Ok(buf)
 }
@@ -1,17 +1,37 @@
-use std::cmp::max;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::ops::{Bound, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::Arc;
 
+use data_encoding::HEXUPPER;
+use ring::digest::{Context, SHA256};
+use sea_orm::prelude::DateTimeUtc;
 use sea_orm::DatabaseConnection;
+use serde::Serialize;
 
 use tantivy::collector::Count;
+use tantivy::collector::DocSetCollector;
 use tantivy::collector::FilterCollector;
 use tantivy::collector::TopDocs;
+use tantivy::collector::{FacetCollector, FacetCounts};
+use tantivy::columnar::{ColumnValues, StrColumn};
+use tantivy::directory::MmapDirectory;
 use tantivy::doc;
 use tantivy::query::AllQuery;
-use tantivy::query::QueryParser;
+use tantivy::query::{
+    BooleanQuery, BoostQuery, ConstScoreQuery, MoreLikeThisQuery, Occur, Query, RangeQuery,
+    TermQuery, TermSetQuery,
+};
+use tantivy::query::{QueryParser, QueryParserError};
+use tantivy::query_grammar::{self, Delimiter, UserInputAst, UserInputLeaf, UserInputLiteral};
 use tantivy::schema::*;
-use tantivy::tokenizer::StopWordFilter;
-use tantivy::tokenizer::TextAnalyzer;
+use tantivy::tokenizer::TokenizerManager;
+use tantivy::DateTime;
+use tantivy::DocAddress;
+use tantivy::DocSet;
 use tantivy::DocId;
 use tantivy::Index;
 use tantivy::IndexReader;
@@ -19,15 +39,31 @@ use tantivy::IndexWriter;
 use tantivy::Opstamp;
 use tantivy::ReloadPolicy;
 use tantivy::Score;
+use tantivy::Searcher;
 use tantivy::SegmentReader;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use tokio::sync::RwLock;
+use tantivy::SnippetGenerator;
+use tokio::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, remove_file, rename, write};
+use tokio::sync::{Mutex, RwLock};
 use tokio::time;
 use tokio::time::sleep;
 
-use crate::database::query::get_all_txt;
+use crate::database::analyzer::{
+    build_pinyin_analyzer, build_prefix_analyzer, AnalyzerOptions, SharedJieba,
+    TITLE_PREFIX_MAX_CHARS,
+};
+use crate::database::query::{get_all_folder_paths, get_all_txt, get_all_txt_tags};
 use crate::database::query::read_file;
+use crate::entities::{synonym, txt, user_dict};
+
+pub const INDEXDIR: &str = "index";
+// 索引根目录中记录当前使用的子目录的文件
+const CURRENT_FILE: &str = "current";
+// 分词器的实现改变时加一，已有的索引需要重建
+const ANALYZER_VERSION: u32 = 4;
+// 补全词使用的分词器
+const SUGGEST_TOKENIZER: &str = "jieba_suggest";
+// 拼音域使用的分词器
+const PINYIN_TOKENIZER: &str = "pinyin";
 
 #[derive(Clone, Copy)]
 pub struct Fields {
@@ -36,275 +72,1580 @@ pub struct Fields {
     // 搜索域
     pub title: Field,
     pub body: Field,
+    // 标题和正文的拼音
+    pub pinyin: Field,
+    // 标题的前缀，用于输入时补全
+    pub title_prefix: Field,
+    // 标题和正文中未提取词干的词，用于输入时补全，见suggest_term
+    pub suggest_term: Field,
     // 权限控制
     pub level: Field,
+    // 所有者
+    pub user_id: Field,
+    // 排序和范围过滤
+    pub title_sort: Field,
+    pub format: Field,
+    // 标签，facet为/标签名
+    pub tag: Field,
+    // 所在文件夹，facet为从顶层开始的文件夹id路径，如/1/5
+    pub folder: Field,
+    pub created_at: Field,
+    pub updated_at: Field,
+    pub size_bytes: Field,
+    pub char_count: Field,
+}
+
+impl Fields {
+    fn from_schema(schema: &Schema) -> anyhow::Result<Self> {
+        Ok(Self {
+            id: schema.get_field("id")?,
+            title: schema.get_field("title")?,
+            body: schema.get_field("body")?,
+            pinyin: schema.get_field("pinyin")?,
+            title_prefix: schema.get_field("title_prefix")?,
+            suggest_term: schema.get_field("suggest_term")?,
+            level: schema.get_field("level")?,
+            user_id: schema.get_field("user_id")?,
+            title_sort: schema.get_field("title_sort")?,
+            format: schema.get_field("format")?,
+            tag: schema.get_field("tag")?,
+            folder: schema.get_field("folder")?,
+            created_at: schema.get_field("created_at")?,
+            updated_at: schema.get_field("updated_at")?,
+            size_bytes: schema.get_field("size_bytes")?,
+            char_count: schema.get_field("char_count")?,
+        })
+    }
+
+    /// 由数据库中的文档信息、标签、文件夹、正文和正文中的词构造索引文档
+    fn to_doc(
+        self,
+        txt: &txt::Model,
+        meta: &DocMeta,
+        body: String,
+        terms: &BTreeSet<String>,
+    ) -> Document {
+        let mut doc = doc!(
+            self.id => txt.id,
+            self.title => txt.title.clone(),
+            self.pinyin => txt.title.clone(),
+            self.pinyin => body.clone(),
+            self.title_prefix => txt.title.trim(),
+            self.body => body,
+            self.level => txt.level as u64,
+            self.user_id => txt.user_id,
+            self.title_sort => txt.title.clone(),
+            self.format => txt.format.clone(),
+            self.created_at => DateTime::from_timestamp_secs(txt.created_at.timestamp()),
+            self.updated_at => DateTime::from_timestamp_secs(txt.updated_at.timestamp()),
+            self.size_bytes => txt.size_bytes,
+            self.char_count => txt.char_count
+        );
+        for term in terms {
+            doc.add_text(self.suggest_term, suggest_term(term, txt.level));
+        }
+        for tag in &meta.tags {
+            doc.add_facet(self.tag, Facet::from_path([tag]));
+        }
+        if !meta.folder_path.is_empty() {
+            doc.add_facet(self.folder, folder_facet(&meta.folder_path));
+        }
+        doc
+    }
+}
+
+/// 文档在数据库中其他表里的信息
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocMeta {
+    pub tags: Vec<String>,
+    // 所在文件夹的路径，从顶层的文件夹开始，不在文件夹中时为空
+    pub folder_path: Vec<u64>,
+}
+
+/// 启动时比较索引与数据库用的文档信息：id、level、所有者、修改时间（秒）、标签、文件夹路径
+type SyncKey = (u64, u64, u64, i64, Vec<String>, Vec<u64>);
+
+/// 补全词域中的词：词后面接\0和文档的level，
+/// 以前缀查找词时同一个词的各个level相邻，按level累加文档频率即可得到用户可见的文档数
+fn suggest_term(term: &str, level: u8) -> String {
+    format!("{term}\0{level}")
+}
+
+/// 补全词域中的词和level
+fn split_suggest_term(key: &[u8]) -> Option<(&str, u8)> {
+    let (term, level) = std::str::from_utf8(key).ok()?.rsplit_once('\0')?;
+    Some((term, level.parse().ok()?))
+}
+
+/// 文件夹路径对应的facet，facet包含其所有上级，所以可以匹配整个子树
+fn folder_facet(path: &[u64]) -> Facet {
+    Facet::from_path(path.iter().map(|id| id.to_string()))
+}
+
+/// 高亮片段的参数
+#[derive(Clone, Debug)]
+pub struct SnippetOptions {
+    // 片段最大字符数
+    pub fragment_size: usize,
+    // 高亮标记
+    pub pre_tag: String,
+    pub post_tag: String,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            fragment_size: 150,
+            pre_tag: "<b>".to_string(),
+            post_tag: "</b>".to_string(),
+        }
+    }
+}
+
+/// 命中文档的高亮片段，没有命中的域为None
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Highlight {
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
+
+/// 一条搜索结果
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub id: u64,
+    pub score: f32,
+    pub highlight: Option<Highlight>,
+}
+
+/// 命中的文档中某个标签的文档数
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// 一页搜索结果，total为命中总数，tags为所有命中的文档的标签统计
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchPage {
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+    pub tags: Vec<TagCount>,
+}
+
+/// 补全的标题
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TitleSuggestion {
+    pub id: u64,
+    pub title: String,
+}
+
+/// 补全的词，count为用户可见的包含它的文档数
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TermSuggestion {
+    pub term: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Suggestions {
+    pub titles: Vec<TitleSuggestion>,
+    pub terms: Vec<TermSuggestion>,
+}
+
+/// 排序依据
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortBy {
+    #[default]
+    Relevance,
+    Title,
+    Level,
+    Owner,
+    Created,
+    Size,
+}
+
+impl From<&str> for SortBy {
+    fn from(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "title" => SortBy::Title,
+            "level" => SortBy::Level,
+            "owner" => SortBy::Owner,
+            "created" => SortBy::Created,
+            "size" => SortBy::Size,
+            _ => SortBy::Relevance,
+        }
+    }
+}
+
+/// 排序方向，/query和/doc未指定或无法识别时都为desc
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl From<&str> for SortOrder {
+    fn from(value: &str) -> Self {
+        if value.eq_ignore_ascii_case("asc") {
+            SortOrder::Asc
+        } else {
+            SortOrder::Desc
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sort {
+    pub by: SortBy,
+    pub order: SortOrder,
+}
+
+/// 搜索参数
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    pub field: SearchField,
+    // 用户的level，只能搜到level不超过它的文档
+    pub level: u8,
+    // 分页，limit为0时只统计命中数，offset + limit不能超过MAX_QUERY_WINDOW
+    pub offset: usize,
+    pub limit: usize,
+    pub sort: Sort,
+    pub filter: SearchFilter,
+    // 模糊匹配的编辑距离，为None时只做精确匹配
+    pub fuzzy: Option<u8>,
+    // 是否用同义词扩展查询
+    pub synonyms: bool,
+    // 为Some时生成高亮片段
+    pub snippet: Option<SnippetOptions>,
+    // 用户通过授权可以查看的文档id，不受level限制
+    pub granted: Vec<u64>,
+}
+
+/// 分页时offset + limit的上限，TopDocs会按offset + limit分配堆
+pub const MAX_QUERY_WINDOW: usize = 10_000;
+
+/// 模糊匹配允许的最大编辑距离
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+// 模糊匹配的权重，使其排在精确匹配之后
+const FUZZY_BOOST: Score = 0.5;
+
+// 同义词的权重，使其排在原词之后
+const SYNONYM_BOOST: Score = 0.5;
+// 同义词最多由几个词组成
+const MAX_SYNONYM_TOKENS: usize = 4;
+
+/// 同义词缓存，map的键为分词后的词序列，分词器改变后需要刷新
+#[derive(Default)]
+struct Synonyms {
+    pairs: Vec<(String, String)>,
+    map: HashMap<Vec<String>, Vec<String>>,
+}
+
+// 相似文档查询最多使用的词数
+const MLT_MAX_QUERY_TERMS: usize = 25;
+
+/// 结构化过滤条件，与文本查询同时生效
+#[derive(Clone, Debug, Default)]
+pub struct SearchFilter {
+    // 所有者id，为空时不过滤
+    pub owners: Vec<u64>,
+    // level范围，上限不会超过用户自己的level
+    pub min_level: Option<u8>,
+    pub max_level: Option<u8>,
+    // 创建时间范围，左闭右开
+    pub created_from: Option<DateTimeUtc>,
+    pub created_to: Option<DateTimeUtc>,
+    // 文档格式，为空时不过滤
+    pub formats: Vec<String>,
+    // 标签，有其中任意一个即可，为空时不过滤
+    pub tags: Vec<String>,
+    // 文件夹的路径，只保留在其中或其子文件夹中的文档
+    pub folder_path: Option<Vec<u64>>,
+}
+
+impl SearchFilter {
+    /// 用户在这次搜索中能看到的level范围
+    fn level_range(&self, level: u8) -> RangeInclusive<u64> {
+        let max = self.max_level.map_or(level, |max| max.min(level));
+        self.min_level.unwrap_or(0) as u64..=max as u64
+    }
+
+    /// 把过滤条件加到文本查询上，过滤条件不参与打分
+    fn apply(&self, fields: &Fields, query: Box<dyn Query>) -> Box<dyn Query> {
+        let mut filters: Vec<Box<dyn Query>> = Vec::new();
+        if !self.owners.is_empty() {
+            let terms = self.owners.iter().map(|id| Term::from_field_u64(fields.user_id, *id));
+            filters.push(Box::new(TermSetQuery::new(terms)));
+        }
+        if self.created_from.is_some() || self.created_to.is_some() {
+            let to_date = |dt: &DateTimeUtc| DateTime::from_timestamp_secs(dt.timestamp());
+            let lower = match &self.created_from {
+                Some(dt) => Bound::Included(to_date(dt)),
+                None => Bound::Unbounded,
+            };
+            let upper = match &self.created_to {
+                Some(dt) => Bound::Excluded(to_date(dt)),
+                None => Bound::Unbounded,
+            };
+            filters.push(Box::new(RangeQuery::new_date_bounds(
+                "created_at".to_string(),
+                lower,
+                upper,
+            )));
+        }
+        if !self.formats.is_empty() {
+            let terms = self.formats.iter().map(|f| Term::from_field_text(fields.format, f));
+            filters.push(Box::new(TermSetQuery::new(terms)));
+        }
+        if !self.tags.is_empty() {
+            let terms = self
+                .tags
+                .iter()
+                .map(|tag| Term::from_facet(fields.tag, &Facet::from_path([tag])));
+            filters.push(Box::new(TermSetQuery::new(terms)));
+        }
+        if let Some(path) = &self.folder_path {
+            filters.push(Box::new(TermQuery::new(
+                Term::from_facet(fields.folder, &folder_facet(path)),
+                IndexRecordOption::Basic,
+            )));
+        }
+        if filters.is_empty() {
+            return query;
+        }
+
+        let mut clauses = vec![(Occur::Must, query)];
+        for filter in filters {
+            let filter: Box<dyn Query> = Box::new(ConstScoreQuery::new(filter, 0.0));
+            clauses.push((Occur::Must, filter));
+        }
+        Box::new(BooleanQuery::new(clauses))
+    }
+}
+
+/// 排序键，同一次搜索中只会出现同一种
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+enum SortKey {
+    Score(Score),
+    Num(u64),
+    Date(i64),
+    Text(String),
+}
+
+/// 按排序键比较，相同时按得分从高到低
+#[derive(Clone, Debug, PartialEq)]
+struct SortValue {
+    key: SortKey,
+    score: Score,
+    asc: bool,
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let ord = self.key.partial_cmp(&other.key)?;
+        let ord = if self.asc { ord.reverse() } else { ord };
+        Some(ord.then(self.score.partial_cmp(&other.score)?))
+    }
 }
-static mut INDEX: Option<&Index> = None;
-static mut WRITER: Option<Arc<RwLock<IndexWriter>>> = None;
-static mut READER: Option<&IndexReader> = None;
-static mut FIELDS: Option<&Fields> = None;
 
-pub fn get_index() -> &'static Index {
-    unsafe { INDEX.expect("NO INDEX!!!") }
+/// 从快速域读取某个段中文档的排序键
+enum SortKeyReader {
+    Score,
+    Num(Arc<dyn ColumnValues<u64>>),
+    Date(Arc<dyn ColumnValues<DateTime>>),
+    Text(Option<StrColumn>),
 }
-pub fn get_writer() -> Arc<RwLock<IndexWriter>> {
-    unsafe { WRITER.clone().expect("NO WRITER!!!") }
+
+impl SortKeyReader {
+    fn new(segment_reader: &SegmentReader, by: SortBy) -> Self {
+        let fast_fields = segment_reader.fast_fields();
+        let u64_reader = |name: &str| {
+            SortKeyReader::Num(fast_fields.u64(name).unwrap().first_or_default_col(0))
+        };
+        match by {
+            SortBy::Relevance => SortKeyReader::Score,
+            SortBy::Title => SortKeyReader::Text(fast_fields.str("title_sort").unwrap()),
+            SortBy::Level => u64_reader("level"),
+            SortBy::Owner => u64_reader("user_id"),
+            SortBy::Size => u64_reader("size_bytes"),
+            SortBy::Created => SortKeyReader::Date(
+                fast_fields
+                    .date("created_at")
+                    .unwrap()
+                    .first_or_default_col(DateTime::MIN),
+            ),
+        }
+    }
+
+    fn key(&self, doc: DocId, score: Score) -> SortKey {
+        match self {
+            SortKeyReader::Score => SortKey::Score(score),
+            SortKeyReader::Num(reader) => SortKey::Num(reader.get_val(doc)),
+            SortKeyReader::Date(reader) => SortKey::Date(reader.get_val(doc).into_timestamp_secs()),
+            SortKeyReader::Text(reader) => {
+                let mut title = String::new();
+                if let Some(reader) = reader {
+                    if let Some(ord) = reader.term_ords(doc).next() {
+                        let _ = reader.ord_to_str(ord, &mut title);
+                    }
+                }
+                SortKey::Text(title)
+            }
+        }
+    }
+}
+
+/// 一个索引及其读写器，重建时在新的索引中建立，完成后整体替换
+struct IndexHandle {
+    index: Index,
+    reader: IndexReader,
+    writer: RwLock<IndexWriter>,
+    // 索引所在的子目录，在内存中时为None
+    dir: Option<PathBuf>,
+    // 建立索引时分词配置的版本，每次提交时写入索引的元信息
+    version: String,
 }
 
-pub fn get_reader() -> &'static IndexReader {
-    unsafe {
-        let reader = READER.expect("NO READER!!!");
-        reader
+impl IndexHandle {
+    fn new(
+        mut index: Index,
+        tokenizers: &TokenizerManager,
+        dir: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        // 所有索引共享分词器，修改停用词等对新旧索引同时生效
+        index.set_tokenizers(tokenizers.clone());
+        let version = index.load_metas()?.payload.unwrap_or_default();
+        let writer = index.writer(1024 * 1024 * 50)?;
+        let reader: IndexReader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        Ok(Self {
+            index,
+            reader,
+            writer: RwLock::new(writer),
+            dir,
+            version,
+        })
+    }
+
+    /// 提交索引并写入分词配置的版本，之后立即刷新reader
+    async fn commit(&self) -> anyhow::Result<Opstamp> {
+        let mut writer_w = self.writer.write().await;
+        let mut prepared = writer_w.prepare_commit()?;
+        prepared.set_payload(&self.version);
+        let opstamp = prepared.commit()?;
+        self.reader.reload()?;
+        Ok(opstamp)
     }
 }
 
-pub fn get_fields() -> Fields {
-    unsafe { FIELDS.expect("NO FILEDS!!!").clone() }
+/// 重建期间对旧索引的修改，替换前在新索引上重放
+enum IndexOp {
+    Add(u64, Document),
+    Delete(u64),
 }
 
-async fn get_stopwords() -> Vec<String> {
-    let path = "resource/stopword.txt";
-    let mut f = File::open(path).await.unwrap();
-    let mut ss = String::with_capacity(1400 * 3);
-    let _ = f.read_to_string(&mut ss).await;
-    let res: Vec<&str> = ss.split(char::is_whitespace).collect();
-    let mut stopwords = Vec::with_capacity(1400);
-    for word in res {
-        stopwords.push(word.to_string());
+/// 全文检索服务，持有索引、读写器和域，由AppState持有
+#[derive(Clone)]
+pub struct SearchService {
+    handle: Arc<std::sync::RwLock<Arc<IndexHandle>>>,
+    tokenizers: TokenizerManager,
+    fields: Fields,
+    options: AnalyzerOptions,
+    // 所有分词器共享的jieba词典
+    jieba: SharedJieba,
+    synonyms: Arc<std::sync::RwLock<Synonyms>>,
+    // 当前停用词的摘要
+    stopwords_digest: Arc<std::sync::RwLock<String>>,
+    // 当前用户词典的摘要
+    user_dict_digest: Arc<std::sync::RwLock<String>>,
+    // 已安排但尚未开始的重建
+    rebuild_pending: Arc<AtomicBool>,
+    // 同时只进行一次重建
+    rebuilding: Arc<Mutex<()>>,
+    // 重建期间为Some，记录对旧索引的修改
+    pending_ops: Arc<Mutex<Option<Vec<IndexOp>>>>,
+}
+
+impl fmt::Debug for SearchService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchService")
+            .field("index", &self.handle().index)
+            .finish_non_exhaustive()
     }
-    stopwords
 }
 
-pub async fn init_index() {
-    println!("-->> {:<12} -- start to init", "INIT_INDEX");
-    // 开始取得停用词
-    let jh = tokio::spawn(get_stopwords());
+/// 一组词的摘要，用作分词配置的版本
+fn digest(words: &[String]) -> String {
+    let mut words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    words.sort_unstable();
+    let mut context = Context::new(&SHA256);
+    for word in words {
+        context.update(word.as_bytes());
+        context.update(b"\n");
+    }
+    HEXUPPER.encode(&context.finish().as_ref()[..8])
+}
 
+fn build_schema(options: AnalyzerOptions) -> Schema {
     let mut schema_builder = Schema::builder();
 
     let text_field_indexing = TextFieldIndexing::default()
-        .set_tokenizer("jieba")
+        .set_tokenizer(&options.tokenizer())
         .set_index_option(IndexRecordOption::WithFreqsAndPositions);
-    let text_options = TextOptions::default().set_indexing_options(text_field_indexing);
+    // 存储原文，用于生成高亮片段
+    let text_options = TextOptions::default()
+        .set_indexing_options(text_field_indexing)
+        .set_stored();
 
-    schema_builder.add_u64_field("id", INDEXED | STORED);
+    schema_builder.add_u64_field("id", INDEXED | STORED | FAST);
     schema_builder.add_text_field("title", text_options.clone());
-    schema_builder.add_text_field("body", text_options.clone());
+    schema_builder.add_text_field("body", text_options);
+    // 拼音只用于检索，不存储
+    let pinyin_indexing = TextFieldIndexing::default()
+        .set_tokenizer(PINYIN_TOKENIZER)
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    schema_builder.add_text_field(
+        "pinyin",
+        TextOptions::default().set_indexing_options(pinyin_indexing),
+    );
+    let prefix_indexing = TextFieldIndexing::default()
+        .set_tokenizer("title_prefix")
+        .set_index_option(IndexRecordOption::Basic);
+    schema_builder.add_text_field(
+        "title_prefix",
+        TextOptions::default().set_indexing_options(prefix_indexing),
+    );
+    // 由补全词的分词器在构造文档时分词，见Fields::to_doc
+    schema_builder.add_text_field("suggest_term", STRING);
     schema_builder.add_u64_field("level", INDEXED | FAST);
+    schema_builder.add_u64_field("user_id", INDEXED | FAST);
+    // 整个标题作为一个词，用于排序
+    schema_builder.add_text_field("title_sort", STRING | FAST);
+    schema_builder.add_text_field("format", STRING);
+    schema_builder.add_facet_field("tag", FacetOptions::default());
+    schema_builder.add_facet_field("folder", FacetOptions::default());
+    schema_builder.add_date_field("created_at", INDEXED | FAST);
+    schema_builder.add_date_field("updated_at", INDEXED | FAST);
+    schema_builder.add_u64_field("size_bytes", INDEXED | FAST);
+    schema_builder.add_u64_field("char_count", INDEXED | FAST);
 
-    let schema = schema_builder.build();
-
-    let id = schema.get_field("id").unwrap();
-    let title = schema.get_field("title").unwrap();
-    let body = schema.get_field("body").unwrap();
-    let level = schema.get_field("level").unwrap();
-    let fields = Fields {
-        id,
-        title,
-        body,
-        level,
+    schema_builder.build()
+}
+
+/// 根目录中当前使用的索引子目录，并删除其它子目录（中断的重建和被替换的旧索引），
+/// 没有记录时（新部署或旧的目录结构）清空根目录
+async fn current_index_dir(root: &Path) -> anyhow::Result<PathBuf> {
+    create_dir_all(root).await?;
+    let current = read_to_string(root.join(CURRENT_FILE))
+        .await
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    let current = match current {
+        Some(name) => name,
+        None => {
+            println!("-->> {:<12} -- no {CURRENT_FILE} in {root:?}, clear it", "OPEN_INDEX");
+            remove_dir_all(root).await?;
+            create_dir_all(root).await?;
+            let dir = root.join("0");
+            set_current_index_dir(&dir).await?;
+            return Ok(dir);
+        }
     };
-    // 获得停用词
-    let stopwords = jh.await.unwrap();
-    println!("-->> {:<12} -- have {} stopwords", "INIT_INDEX", stopwords.len());
-    // 建立索引
-    let jieba = tantivy_jieba::JiebaTokenizer {};
-    let tokenizer = TextAnalyzer::builder(jieba)
-        .filter(StopWordFilter::remove(stopwords))
-        .build();
-    let index = Index::create_in_ram(schema.clone());
-    index.tokenizers().register("jieba", tokenizer);
-
-    let writer = index.writer(1024 * 1024 * 50).unwrap();
-    let writer: Arc<RwLock<IndexWriter>> = Arc::new(RwLock::new(writer));
-    let reader: tantivy::IndexReader = index
-        .reader_builder()
-        .reload_policy(ReloadPolicy::OnCommit)
-        .try_into()
-        .unwrap();
-
-    // 设置全局变量
-
-    unsafe {
-        let index = Box::new(index);
-        INDEX = Some(Box::leak(index));
-
-        let reader = Box::new(reader);
-        READER = Some(Box::leak(reader));
-
-        WRITER = Some(writer);
-
-        let fields = Box::new(fields);
-        FIELDS = Some(Box::leak(fields));
-    }
-    println!("-->> {:<12} -- finish", "INIT_INDEX");
-}
-
-pub async fn rebuild_search_index(conn: DatabaseConnection) {
-    println!("-->> {:<12} -- rebuiding index", "REBUILD_INDEX");
-
-    let writer = get_writer();
-    let mut writer_w = writer.write().await;
-    let _ = writer_w.delete_all_documents();
-    let fields = get_fields();
-
-    let txts = get_all_txt(&conn).await.unwrap();
-    let mut id_title_and_join_handlers = Vec::with_capacity(512);
-
-    for txt in txts {
-        let id = txt.id;
-        let title = txt.title;
-        let level = txt.level;
-        let read_file = read_file(txt.hash);
-        id_title_and_join_handlers.push((id, title, level, tokio::spawn(read_file)));
-    }
-
-    let mut count = 0;
-    for (id, title, level, jh) in id_title_and_join_handlers {
-        let body = jh.await.unwrap().unwrap();
-        let _ = writer_w.add_document(doc!(
-            fields.id => id,
-            fields.title => title,
-            fields.body => body,
-            fields.level => level as u64
-        ));
-        count += 1;
-        if count == 10 {
-            count = 0;
-            let _ = writer_w.commit();
-        }
-    }
-    let _ = writer_w.commit();
-    println!("-->> {:<12} -- finish", "REBUILD_INDEX");
-}
-
-pub async fn commiting() {
-    let writer = get_writer();
-    loop {
-        let opstamp: Opstamp = {
-            let mut writer_w = writer.write().await;
-            writer_w.commit().unwrap()
-        };
-        println!(
-            "-->> {:<12} -- committed with opstamp {opstamp:?}",
-            "COMMITING"
-        );
-        sleep(time::Duration::from_secs(5)).await;
+
+    let mut entries = read_dir(root).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if name == CURRENT_FILE || name == current.as_str() {
+            continue;
+        }
+        println!("-->> {:<12} -- remove stale {:?}", "OPEN_INDEX", entry.path());
+        if entry.file_type().await?.is_dir() {
+            remove_dir_all(entry.path()).await?;
+        } else {
+            remove_file(entry.path()).await?;
+        }
     }
+    Ok(root.join(current))
 }
 
-#[derive(Clone, Copy)]
-pub enum SearchField {
-    Title,
-    Body,
-    All,
+/// 把dir记为根目录中当前使用的索引，先写临时文件再改名，中途退出时不会损坏
+async fn set_current_index_dir(dir: &Path) -> anyhow::Result<()> {
+    let (Some(root), Some(name)) = (dir.parent(), dir.file_name()) else {
+        return Err(anyhow::Error::msg(format!("invalid index dir {dir:?}")));
+    };
+    let tmp = root.join(format!("{CURRENT_FILE}.tmp"));
+    write(&tmp, name.to_string_lossy().as_bytes()).await?;
+    rename(&tmp, root.join(CURRENT_FILE)).await?;
+    Ok(())
 }
 
-impl From<String> for SearchField {
-    fn from(value: String) -> Self {
-        if value.eq_ignore_ascii_case("title") {
-            SearchField::Title
-        } else if value.eq_ignore_ascii_case("body") {
-            SearchField::Body
-        } else {
-            SearchField::All
+/// 重建时使用的新子目录，与当前的子目录在同一个根目录中
+fn next_index_dir(dir: &Path) -> PathBuf {
+    let next = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse::<u64>().ok())
+        .map_or(0, |n| n + 1);
+    dir.with_file_name(next.to_string())
+}
+
+/// 打开磁盘上的索引，不存在或schema不一致时重新创建
+async fn open_index_in_dir(dirpath: &Path, schema: Schema) -> anyhow::Result<Index> {
+    create_dir_all(dirpath).await?;
+    let dir = MmapDirectory::open(dirpath)?;
+    if Index::exists(&dir)? {
+        let index = Index::open(dir)?;
+        if index.schema() == schema {
+            println!("-->> {:<12} -- open index in {dirpath:?}", "OPEN_INDEX");
+            return Ok(index);
         }
+        // schema改变，旧索引作废
+        println!("-->> {:<12} -- schema changed, recreate index", "OPEN_INDEX");
+        remove_dir_all(dirpath).await?;
+        create_dir_all(dirpath).await?;
     }
+    println!("-->> {:<12} -- create index in {dirpath:?}", "OPEN_INDEX");
+    let index = Index::create_in_dir(dirpath, schema)?;
+    Ok(index)
 }
 
-impl From<SearchField> for Vec<Field> {
-    fn from(value: SearchField) -> Self {
-        let fields = get_fields();
-        let search_fields = match value {
-            SearchField::Title => vec![fields.title],
-            SearchField::Body => vec![fields.body],
-            SearchField::All => vec![fields.body, fields.title],
+impl SearchService {
+    /// 打开（或创建）位于dirpath的索引，索引在dirpath的子目录中，重建时整体替换
+    pub async fn open(dirpath: impl AsRef<Path>) -> anyhow::Result<Self> {
+        println!("-->> {:<12} -- start to init", "INIT_INDEX");
+        let options = AnalyzerOptions::from_env();
+        println!("-->> {:<12} -- {options:?}", "INIT_INDEX");
+        let dir = current_index_dir(dirpath.as_ref()).await?;
+        let index = open_index_in_dir(&dir, build_schema(options)).await?;
+        Self::from_index(index, options, Some(dir)).await
+    }
+
+    /// 在内存中创建索引，用于测试
+    pub async fn create_in_ram() -> anyhow::Result<Self> {
+        Self::create_in_ram_with(AnalyzerOptions::default()).await
+    }
+
+    pub async fn create_in_ram_with(options: AnalyzerOptions) -> anyhow::Result<Self> {
+        Self::from_index(Index::create_in_ram(build_schema(options)), options, None).await
+    }
+
+    async fn from_index(
+        index: Index,
+        options: AnalyzerOptions,
+        dir: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let fields = Fields::from_schema(&index.schema())?;
+        let tokenizers = TokenizerManager::default();
+        tokenizers.register(
+            "title_prefix",
+            build_prefix_analyzer(options.fold_traditional),
+        );
+        tokenizers.register(PINYIN_TOKENIZER, build_pinyin_analyzer());
+        let handle = IndexHandle::new(index, &tokenizers, dir)?;
+
+        println!("-->> {:<12} -- finish", "INIT_INDEX");
+        let search = Self {
+            handle: Arc::new(std::sync::RwLock::new(Arc::new(handle))),
+            tokenizers,
+            fields,
+            options,
+            jieba: SharedJieba::default(),
+            synonyms: Default::default(),
+            stopwords_digest: Default::default(),
+            user_dict_digest: Default::default(),
+            rebuild_pending: Arc::new(AtomicBool::new(false)),
+            rebuilding: Default::default(),
+            pending_ops: Default::default(),
         };
-        search_fields
+        // 停用词由数据库加载，见stopword::load_stopwords
+        search.set_stopwords(Vec::new());
+        Ok(search)
     }
-}
 
-pub fn count_doc(level: u8) -> anyhow::Result<usize> {
-    let fields = get_fields();
+    /// 当前使用的索引
+    fn handle(&self) -> Arc<IndexHandle> {
+        self.handle.read().unwrap().clone()
+    }
 
-    let reader = get_reader();
-    let searcher = reader.searcher();
+    /// 当前分词配置的版本，与索引中记录的不同时索引需要重建
+    fn analysis_version(&self) -> String {
+        format!(
+            "{ANALYZER_VERSION}/{}/{}",
+            self.stopwords_digest.read().unwrap(),
+            self.user_dict_digest.read().unwrap()
+        )
+    }
 
-    let query = AllQuery;
-    let filter = FilterCollector::new(fields.level, move |v: u64| v <= level as u64, Count);
-    let count = searcher.search(&query, &filter)?;
-    Ok(count)
-}
+    /// 用新的停用词注册分词器，之后索引和查询都使用新的分词器，
+    /// 已有的索引需要重建
+    pub fn set_stopwords(&self, stopwords: Vec<String>) {
+        *self.stopwords_digest.write().unwrap() = digest(&stopwords);
+        let tokenizers = &self.tokenizers;
+        tokenizers.register(
+            &self.options.tokenizer(),
+            self.options.build_analyzer(self.jieba.clone(), stopwords.clone()),
+        );
+        tokenizers.register(
+            SUGGEST_TOKENIZER,
+            self.options
+                .build_suggest_analyzer(self.jieba.clone(), stopwords),
+        );
+        self.refresh_synonyms();
+    }
 
-pub fn search_from_rev_index(
-    field: SearchField,
-    query_string: &str,
-    level: u8,
-    limit: usize,
-) -> anyhow::Result<Vec<(u64, f32)>> {
-    // 若limit为0，自动设置limit值
-    let limit = if limit == 0 {
-        max(1, count_doc(level)?)
-    } else {
-        limit
-    };
+    /// 替换jieba的用户词典，之后的查询立即生效，已有的索引需要重建
+    pub fn set_user_dict(&self, words: &[user_dict::Model]) {
+        // 词频和词性也影响分词
+        let entries: Vec<String> = words
+            .iter()
+            .map(|word| format!("{} {:?} {:?}", word.word, word.freq, word.tag))
+            .collect();
+        *self.user_dict_digest.write().unwrap() = digest(&entries);
+        self.jieba.set_user_dict(words);
+        self.refresh_synonyms();
+    }
 
-    let fields = get_fields();
-    let search_fields = Vec::from(field);
+    /// 替换同义词，只影响查询，不需要重建索引
+    pub fn set_synonyms(&self, synonyms: &[synonym::Model]) {
+        self.synonyms.write().unwrap().pairs = synonyms
+            .iter()
+            .map(|s| (s.word.clone(), s.synonym.clone()))
+            .collect();
+        self.refresh_synonyms();
+    }
+
+    /// 用当前的分词器对同义词重新分词
+    fn refresh_synonyms(&self) {
+        let pairs = self.synonyms.read().unwrap().pairs.clone();
+        let mut map: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+        for (word, synonym) in pairs {
+            let key = self.analyze(&word);
+            if key.is_empty() || key.len() > MAX_SYNONYM_TOKENS {
+                continue;
+            }
+            map.entry(key).or_default().push(synonym);
+        }
+        self.synonyms.write().unwrap().map = map;
+    }
+
+    /// 用正文的分词器把文本转为词序列
+    fn analyze(&self, text: &str) -> Vec<String> {
+        self.analyze_with(&self.options.tokenizer(), text)
+    }
+
+    fn analyze_with(&self, tokenizer: &str, text: &str) -> Vec<String> {
+        let mut analyzer = match self.tokenizers.get(tokenizer) {
+            Some(analyzer) => analyzer,
+            None => return Vec::new(),
+        };
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        tokens
+    }
+
+    /// 一个词中出现的同义词
+    fn synonyms_of(&self, synonyms: &Synonyms, phrase: &str) -> Vec<String> {
+        let tokens = self.analyze(phrase);
+        let mut res = Vec::new();
+        for start in 0..tokens.len() {
+            for end in start + 1..=tokens.len().min(start + MAX_SYNONYM_TOKENS) {
+                if let Some(words) = synonyms.map.get(&tokens[start..end]) {
+                    res.extend(words.iter().cloned());
+                }
+            }
+        }
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+
+    /// 把查询中的每个词替换为（原词 或 同义词短语），同义词的权重低于原词，
+    /// 这样查询中的+、-对同义词的命中同样有效；排除的词不扩展
+    fn expand_synonyms(&self, ast: UserInputAst, synonyms: &Synonyms, excluded: bool) -> UserInputAst {
+        let literal = match ast {
+            UserInputAst::Clause(clauses) => {
+                return UserInputAst::Clause(
+                    clauses
+                        .into_iter()
+                        .map(|(occur, ast)| {
+                            let excluded = excluded || occur == Some(Occur::MustNot);
+                            (occur, self.expand_synonyms(ast, synonyms, excluded))
+                        })
+                        .collect(),
+                )
+            }
+            UserInputAst::Boost(ast, boost) => {
+                let ast = self.expand_synonyms(*ast, synonyms, excluded);
+                return UserInputAst::Boost(Box::new(ast), boost);
+            }
+            UserInputAst::Leaf(leaf) => match *leaf {
+                UserInputLeaf::Literal(literal) if !excluded => literal,
+                leaf => return UserInputAst::from(leaf),
+            },
+        };
+        let words = self.synonyms_of(synonyms, &literal.phrase);
+        if words.is_empty() {
+            return UserInputAst::from(UserInputLeaf::from(literal));
+        }
+        let mut clauses = Vec::with_capacity(words.len() + 1);
+        for word in words {
+            // 短语的得分是各个词得分之和，按词数折算，使多字的同义词不会排在原词之前
+            let token_count = self.analyze(&word).len().max(1);
+            let boost = SYNONYM_BOOST / token_count as Score;
+            let phrase = UserInputLiteral {
+                field_name: literal.field_name.clone(),
+                phrase: word.replace('"', " "),
+                delimiter: Delimiter::DoubleQuotes,
+                slop: 0,
+                prefix: false,
+            };
+            let phrase = UserInputAst::from(UserInputLeaf::from(phrase));
+            clauses.push((Some(Occur::Should), UserInputAst::Boost(Box::new(phrase), boost as f64)));
+        }
+        clauses.insert(0, (Some(Occur::Should), UserInputAst::from(UserInputLeaf::from(literal))));
+        UserInputAst::Clause(clauses)
+    }
+
+    /// 在后台重建索引，已有等待中的重建时不再重复安排
+    pub fn schedule_rebuild(&self, conn: DatabaseConnection) {
+        if self.rebuild_pending.swap(true, atomic::Ordering::SeqCst) {
+            return;
+        }
+        let search = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = search.rebuild(&conn).await {
+                println!("-->> {:<12} -- {e:?}", "REBUILD_INDEX");
+            }
+        });
+    }
 
-    let reader = get_reader();
-    let searcher = reader.searcher();
+    pub fn fields(&self) -> Fields {
+        self.fields
+    }
 
-    let query_parser = QueryParser::for_index(get_index(), search_fields);
-    let query = query_parser.parse_query(query_string)?;
+    /// 检查索引与txt表是否一致（比较文档id、level、所有者、修改时间、标签和所在文件夹，
+    /// 提交前退出而丢失的修改也能发现），以及建立索引时的分词配置与当前的是否一致（重建中途退出时不一致）
+    pub async fn out_of_sync(&self, conn: &DatabaseConnection) -> anyhow::Result<bool> {
+        let handle = self.handle();
+        let version = self.analysis_version();
+        if handle.version != version {
+            println!(
+                "-->> {:<12} -- index built with {:?}, current {version:?}",
+                "CHECK_INDEX", handle.version
+            );
+            return Ok(true);
+        }
 
-    let top_doc = TopDocs::with_limit(limit).tweak_score(move |segment_reader: &SegmentReader| {
-        let level_reader = segment_reader
-            .fast_fields()
-            .u64("level")
-            .unwrap()
-            .first_or_default_col(0);
-        let user_level = level;
+        let mut tags = get_all_txt_tags(conn).await?;
+        let folder_paths = get_all_folder_paths(conn).await?;
+        let mut db_docs: Vec<SyncKey> = get_all_txt(conn)
+            .await?
+            .into_iter()
+            .map(|txt| {
+                let mut doc_tags = tags.remove(&txt.id).unwrap_or_default();
+                doc_tags.sort_unstable();
+                let folder_path = txt
+                    .folder_id
+                    .and_then(|id| folder_paths.get(&id).cloned())
+                    .unwrap_or_default();
+                (
+                    txt.id,
+                    txt.level as u64,
+                    txt.user_id,
+                    txt.updated_at.timestamp(),
+                    doc_tags,
+                    folder_path,
+                )
+            })
+            .collect();
+        db_docs.sort_unstable();
 
-        move |doc: DocId, original_score: Score| {
-            let doc_level: u64 = level_reader.get_val(doc);
-            let doc_level: Score = (doc_level as Score + 1.0) / (user_level as Score + 1.0) * 255.0;
-            let level_boost_score =  ((1.0 + doc_level) as Score).log2() / 8.0;
-            level_boost_score * original_score
+        let searcher = handle.reader.searcher();
+        let mut index_docs: Vec<SyncKey> = Vec::with_capacity(db_docs.len());
+        let mut facet = Facet::root();
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let id_reader = fast_fields.u64("id")?.first_or_default_col(0);
+            let level_reader = fast_fields.u64("level")?.first_or_default_col(0);
+            let user_reader = fast_fields.u64("user_id")?.first_or_default_col(0);
+            let updated_reader = fast_fields
+                .date("updated_at")?
+                .first_or_default_col(DateTime::MIN);
+            let tag_reader = segment_reader.facet_reader("tag")?;
+            let folder_reader = segment_reader.facet_reader("folder")?;
+            for doc in segment_reader.doc_ids_alive() {
+                let mut doc_tags = Vec::new();
+                for ord in tag_reader.facet_ords(doc) {
+                    tag_reader.facet_from_ord(ord, &mut facet)?;
+                    doc_tags.extend(facet.to_path().iter().map(|tag| tag.to_string()));
+                }
+                doc_tags.sort_unstable();
+                let mut folder_path = Vec::new();
+                if let Some(ord) = folder_reader.facet_ords(doc).next() {
+                    folder_reader.facet_from_ord(ord, &mut facet)?;
+                    folder_path = facet.to_path().iter().filter_map(|id| id.parse().ok()).collect();
+                }
+                index_docs.push((
+                    id_reader.get_val(doc),
+                    level_reader.get_val(doc),
+                    user_reader.get_val(doc),
+                    updated_reader.get_val(doc).into_timestamp_secs(),
+                    doc_tags,
+                    folder_path,
+                ));
+            }
         }
-    });
+        index_docs.sort_unstable();
+
+        let out_of_sync = db_docs != index_docs;
+        println!(
+            "-->> {:<12} -- {} docs in database, {} docs in index, out of sync: {out_of_sync}",
+            "CHECK_INDEX",
+            db_docs.len(),
+            index_docs.len()
+        );
+        Ok(out_of_sync)
+    }
 
-    let filter = FilterCollector::new(fields.level, move |v: u64| v <= level as u64, top_doc);
-    let docs = searcher.search(&query, &filter)?;
+    /// 在新的索引中重建，完成后替换当前的索引，重建期间搜索仍使用旧的索引
+    pub async fn rebuild(&self, conn: &DatabaseConnection) -> anyhow::Result<()> {
+        let _rebuilding = self.rebuilding.lock().await;
+        println!("-->> {:<12} -- rebuiding index", "REBUILD_INDEX");
+        // 之后的修改需要再次重建
+        self.rebuild_pending.store(false, atomic::Ordering::SeqCst);
+        let version = self.analysis_version();
+        // 从现在起对旧索引的修改都记录下来，替换前重放
+        *self.pending_ops.lock().await = Some(Vec::new());
+        let res = match self.build_index(conn, version).await {
+            Ok(handle) => self.replace_index(handle).await,
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            *self.pending_ops.lock().await = None;
+        }
+        res
+    }
+
+    /// 由数据库和文件建立新的索引，读取失败的文档跳过
+    async fn build_index(
+        &self,
+        conn: &DatabaseConnection,
+        version: String,
+    ) -> anyhow::Result<IndexHandle> {
+        let current = self.handle();
+        let (index, dir) = match &current.dir {
+            Some(dir) => {
+                let dir = next_index_dir(dir);
+                if dir.exists() {
+                    remove_dir_all(&dir).await?;
+                }
+                create_dir_all(&dir).await?;
+                let index = Index::create_in_dir(&dir, current.index.schema())?;
+                (index, Some(dir))
+            }
+            None => (Index::create_in_ram(current.index.schema()), None),
+        };
+        let handle = IndexHandle {
+            version,
+            ..IndexHandle::new(index, &self.tokenizers, dir)?
+        };
+
+        let txts = get_all_txt(conn).await?;
+        let mut tags = get_all_txt_tags(conn).await?;
+        let folder_paths = get_all_folder_paths(conn).await?;
+        let mut txt_and_join_handlers = Vec::with_capacity(512);
+
+        for txt in txts {
+            let read_file = read_file(txt.hash.clone());
+            txt_and_join_handlers.push((txt, tokio::spawn(read_file)));
+        }
+
+        // 新的索引还没有被使用，不需要等待其它写入
+        let writer = handle.writer.read().await;
+        let (mut count, mut skipped) = (0, 0);
+        for (txt, jh) in txt_and_join_handlers {
+            let body = match jh.await? {
+                Ok(body) => body,
+                Err(e) => {
+                    println!("-->> {:<12} -- skip doc {} {e:?}", "REBUILD_INDEX", txt.id);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let meta = DocMeta {
+                tags: tags.remove(&txt.id).unwrap_or_default(),
+                folder_path: txt
+                    .folder_id
+                    .and_then(|id| folder_paths.get(&id).cloned())
+                    .unwrap_or_default(),
+            };
+            writer.add_document(self.to_doc(&txt, &meta, body))?;
+            count += 1;
+        }
+        drop(writer);
+        handle.commit().await?;
+        println!(
+            "-->> {:<12} -- indexed {count} docs, skipped {skipped}",
+            "REBUILD_INDEX"
+        );
+        Ok(handle)
+    }
+
+    /// 在新的索引上重放重建期间的修改，然后替换当前的索引并删除旧索引的目录
+    async fn replace_index(&self, handle: IndexHandle) -> anyhow::Result<()> {
+        let mut pending_ops = self.pending_ops.lock().await;
+        let ops = pending_ops.take().unwrap_or_default();
+        {
+            let writer = handle.writer.read().await;
+            for op in &ops {
+                match op {
+                    IndexOp::Add(id, doc) => {
+                        writer.delete_term(Term::from_field_u64(self.fields.id, *id));
+                        writer.add_document(doc.clone())?;
+                    }
+                    IndexOp::Delete(id) => {
+                        writer.delete_term(Term::from_field_u64(self.fields.id, *id));
+                    }
+                }
+            }
+        }
+        handle.commit().await?;
+        if let Some(dir) = &handle.dir {
+            set_current_index_dir(dir).await?;
+        }
+        let old = std::mem::replace(&mut *self.handle.write().unwrap(), Arc::new(handle));
+        drop(pending_ops);
+        println!(
+            "-->> {:<12} -- replaced index, replayed {} changes",
+            "REBUILD_INDEX",
+            ops.len()
+        );
+
+        // 等旧索引正在进行的提交结束后再删除
+        if let Some(dir) = &old.dir {
+            let _writer = old.writer.write().await;
+            if let Err(e) = remove_dir_all(dir).await {
+                println!("-->> {:<12} -- remove {dir:?} {e:?}", "REBUILD_INDEX");
+            }
+        }
+        println!("-->> {:<12} -- finish", "REBUILD_INDEX");
+        Ok(())
+    }
+
+    /// 提交索引，并立即刷新reader
+    pub async fn commit(&self) -> anyhow::Result<Opstamp> {
+        self.handle().commit().await
+    }
+
+    /// 定时提交索引
+    pub async fn commiting(self) {
+        loop {
+            match self.commit().await {
+                Ok(opstamp) => println!(
+                    "-->> {:<12} -- committed with opstamp {opstamp:?}",
+                    "COMMITING"
+                ),
+                Err(e) => println!("-->> {:<12} -- {e:?}", "COMMITING"),
+            }
+            sleep(time::Duration::from_secs(5)).await;
+        }
+    }
+
+    pub fn count_doc(&self, level: u8) -> anyhow::Result<usize> {
+        let searcher = self.handle().reader.searcher();
+
+        let query = AllQuery;
+        let filter = FilterCollector::new(self.fields.level, move |v: u64| v <= level as u64, Count);
+        let count = searcher.search(&query, &filter)?;
+        Ok(count)
+    }
+
+    /// 解析查询，开启同义词时把同义词加入查询，开启模糊匹配时模糊命中的得分低于精确命中
+    fn parse_query(
+        &self,
+        query_string: &str,
+        search_fields: Vec<Field>,
+        fuzzy: Option<u8>,
+        synonyms: bool,
+    ) -> anyhow::Result<Box<dyn Query>> {
+        let mut ast = query_grammar::parse_query(query_string)
+            .map_err(|_| QueryParserError::SyntaxError(query_string.to_string()))?;
+        if synonyms {
+            let synonyms = self.synonyms.read().unwrap();
+            if !synonyms.map.is_empty() {
+                ast = self.expand_synonyms(ast, &synonyms, false);
+            }
+        }
+
+        let index = &self.handle().index;
+        let query_parser = QueryParser::for_index(index, search_fields.clone());
+        let exact = query_parser.build_query_from_user_input_ast(ast.clone())?;
+        let distance = match fuzzy {
+            Some(distance) if distance > 0 => distance.min(MAX_FUZZY_DISTANCE),
+            _ => return Ok(exact),
+        };
+
+        let mut fuzzy_parser = QueryParser::for_index(index, search_fields.clone());
+        for field in search_fields {
+            fuzzy_parser.set_field_fuzzy(field, false, distance, true);
+        }
+        let fuzzy = fuzzy_parser.build_query_from_user_input_ast(ast)?;
+        Ok(Box::new(BooleanQuery::new(vec![
+            (Occur::Should, exact),
+            (Occur::Should, Box::new(BoostQuery::new(fuzzy, FUZZY_BOOST))),
+        ])))
+    }
+
+    pub fn search(&self, query_string: &str, options: &SearchOptions) -> anyhow::Result<SearchPage> {
+        if options.offset.saturating_add(options.limit) > MAX_QUERY_WINDOW {
+            return Err(anyhow::Error::msg("offset + limit exceeds MAX_QUERY_WINDOW"));
+        }
+        let fields = self.fields;
+        let search_fields = options.field.fields(&fields);
+        let level = options.level;
+
+        let searcher = self.handle().reader.searcher();
+
+        let text_query =
+            self.parse_query(query_string, search_fields, options.fuzzy, options.synonyms)?;
+        let query = options.filter.apply(&fields, text_query.box_clone());
+        let (query, max_level) = self.restrict_to_visible(query, level, &options.granted);
+        let levels = options.filter.level_range(max_level);
+
+        let mut tag_collector = FacetCollector::for_field("tag");
+        tag_collector.add_facet(Facet::root());
+
+        // limit为0时只统计命中数
+        if options.limit == 0 {
+            let filter = FilterCollector::new(
+                fields.level,
+                move |v: u64| levels.contains(&v),
+                (Count, tag_collector),
+            );
+            let (total, tag_counts) = searcher.search(&query, &filter)?;
+            return Ok(SearchPage {
+                total,
+                hits: Vec::new(),
+                tags: count_tags(&tag_counts),
+            });
+        }
+
+        let sort = options.sort;
+        let top_doc = TopDocs::with_limit(options.limit)
+            .and_offset(options.offset)
+            .tweak_score(move |segment_reader: &SegmentReader| {
+                let fast_fields = segment_reader.fast_fields();
+                let level_reader = fast_fields.u64("level").unwrap().first_or_default_col(0);
+                let key_reader = SortKeyReader::new(segment_reader, sort.by);
+                let user_level = level;
+
+                move |doc: DocId, original_score: Score| {
+                    let doc_level: u64 = level_reader.get_val(doc);
+                    let doc_level: Score =
+                        (doc_level as Score + 1.0) / (user_level as Score + 1.0) * 255.0;
+                    let level_boost_score = (1.0 + doc_level).log2() / 8.0;
+                    let score = level_boost_score * original_score;
+                    SortValue {
+                        key: key_reader.key(doc, score),
+                        score,
+                        asc: sort.order == SortOrder::Asc,
+                    }
+                }
+            });
+
+        let filter = FilterCollector::new(
+            fields.level,
+            move |v: u64| levels.contains(&v),
+            (Count, top_doc, tag_collector),
+        );
+        let (total, docs, tag_counts) = searcher.search(&query, &filter)?;
 
-    let mut res: Vec<(u64, f32)> = Vec::new();
-    for (score, doc_add) in docs {
+        // 每个域只创建一次片段生成器
+        let generators = match &options.snippet {
+            Some(options) => {
+                let mut title = SnippetGenerator::create(&searcher, &*text_query, fields.title)?;
+                let mut body = SnippetGenerator::create(&searcher, &*text_query, fields.body)?;
+                title.set_max_num_chars(options.fragment_size);
+                body.set_max_num_chars(options.fragment_size);
+                Some((title, body, options))
+            }
+            None => None,
+        };
+
+        let mut res: Vec<SearchHit> = Vec::with_capacity(docs.len());
+        for (SortValue { score, .. }, doc_add) in docs {
+            // id从快速域读取，只有需要高亮时才读取存储的原文
+            let id = read_id(&searcher, doc_add)?;
+            let highlight = match &generators {
+                Some((title, body, options)) => {
+                    let doc: Document = searcher.doc(doc_add)?;
+                    Some(Highlight {
+                        title: render_snippet(title, &doc, options),
+                        body: render_snippet(body, &doc, options),
+                    })
+                }
+                None => None,
+            };
+            res.push(SearchHit {
+                id,
+                score,
+                highlight,
+            });
+        }
+        Ok(SearchPage {
+            total,
+            hits: res,
+            tags: count_tags(&tag_counts),
+        })
+    }
+
+    /// 有授权时level的限制放到查询中：level不超过用户level，或者是授权的文档，
+    /// 返回查询和收集时level的上限
+    fn restrict_to_visible(
+        &self,
+        query: Box<dyn Query>,
+        level: u8,
+        granted: &[u64],
+    ) -> (Box<dyn Query>, u8) {
+        if granted.is_empty() {
+            return (query, level);
+        }
+        let visible: Vec<(Occur, Box<dyn Query>)> = vec![
+            (
+                Occur::Should,
+                Box::new(RangeQuery::new_u64("level".to_string(), 0..level as u64 + 1)),
+            ),
+            (Occur::Should, Box::new(self.granted_query(granted))),
+        ];
+        let visible = ConstScoreQuery::new(Box::new(BooleanQuery::new(visible)), 0.0);
+        let query = BooleanQuery::new(vec![(Occur::Must, query), (Occur::Must, Box::new(visible))]);
+        (Box::new(query), u8::MAX)
+    }
+
+    fn granted_query(&self, granted: &[u64]) -> TermSetQuery {
+        TermSetQuery::new(granted.iter().map(|id| Term::from_field_u64(self.fields.id, *id)))
+    }
+
+    /// 输入时补全：以prefix开头的标题和索引中的词，
+    /// 只统计level不超过用户level的文档和授权的文档
+    pub fn suggest(
+        &self,
+        prefix: &str,
+        level: u8,
+        granted: &[u64],
+        limit: usize,
+    ) -> anyhow::Result<Suggestions> {
+        let prefix = self.fold_prefix(prefix.trim());
+        if prefix.is_empty() || limit == 0 {
+            return Ok(Suggestions::default());
+        }
+        Ok(Suggestions {
+            titles: self.suggest_titles(&prefix, level, granted, limit)?,
+            terms: self.suggest_terms(&prefix, level, granted, limit)?,
+        })
+    }
+
+    /// 与前缀域的分词器一样繁简转换、转小写
+    fn fold_prefix(&self, text: &str) -> String {
+        let text = text.to_lowercase();
+        if self.options.fold_traditional {
+            fast2s::convert(&text)
+        } else {
+            text
+        }
+    }
+
+    fn suggest_titles(
+        &self,
+        prefix: &str,
+        level: u8,
+        granted: &[u64],
+        limit: usize,
+    ) -> anyhow::Result<Vec<TitleSuggestion>> {
+        let fields = self.fields;
+        let searcher = self.handle().reader.searcher();
+
+        // 前缀域只索引了前几个字，更长的前缀取出后再比较
+        let indexed: String = prefix.chars().take(TITLE_PREFIX_MAX_CHARS).collect();
+        let truncated = indexed.len() < prefix.len();
+        let term = Term::from_field_text(fields.title_prefix, &indexed);
+        let query = Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+        let (query, max_level) = self.restrict_to_visible(query, level, granted);
+        // 标题越短得分越高；前缀被截断时多取一些，比较后再去掉不匹配的
+        let fetch = if truncated { limit * 4 } else { limit };
+        let filter = FilterCollector::new(
+            fields.level,
+            move |v: u64| v <= max_level as u64,
+            TopDocs::with_limit(fetch),
+        );
+
+        let mut res = Vec::with_capacity(limit);
+        for (_, doc_add) in searcher.search(&query, &filter)? {
+            let doc: Document = searcher.doc(doc_add)?;
+            let Some(title) = doc.get_first(fields.title).and_then(|v| v.as_text()) else {
+                continue;
+            };
+            if truncated && !self.fold_prefix(title.trim()).starts_with(prefix) {
+                continue;
+            }
+            res.push(TitleSuggestion {
+                id: read_id(&searcher, doc_add)?,
+                title: title.to_string(),
+            });
+            if res.len() == limit {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    fn suggest_terms(
+        &self,
+        prefix: &str,
+        level: u8,
+        granted: &[u64],
+        limit: usize,
+    ) -> anyhow::Result<Vec<TermSuggestion>> {
+        let fields = self.fields;
+        let searcher = self.handle().reader.searcher();
+
+        // 授权的文档中level超过用户level的，按段分组，段内按DocId排序
+        let mut granted_docs: HashMap<u32, Vec<(DocId, u8)>> = HashMap::new();
+        if !granted.is_empty() {
+            for doc_add in searcher.search(&self.granted_query(granted), &DocSetCollector)? {
+                let doc_level = searcher
+                    .segment_reader(doc_add.segment_ord)
+                    .fast_fields()
+                    .u64("level")?
+                    .first_or_default_col(0)
+                    .get_val(doc_add.doc_id) as u8;
+                if doc_level > level {
+                    granted_docs
+                        .entry(doc_add.segment_ord)
+                        .or_default()
+                        .push((doc_add.doc_id, doc_level));
+                }
+            }
+        }
+        for docs in granted_docs.values_mut() {
+            docs.sort_unstable();
+        }
+
+        // 从各个段的词典中找出以prefix开头的词，只累加level不超过用户level的文档频率，
+        // 不需要逐个词搜索；已删除但尚未合并掉的文档也计算在内。
+        // level更高的词只在段中有授权的文档时才读取倒排表，检查其中是否有授权的文档
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+            let granted_docs = granted_docs
+                .get(&(segment_ord as u32))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let inverted_index = segment_reader.inverted_index(fields.suggest_term)?;
+            let mut stream = inverted_index.terms().range().ge(prefix).into_stream()?;
+            while stream.advance() {
+                if !stream.key().starts_with(prefix.as_bytes()) {
+                    break;
+                }
+                let Some((term, doc_level)) = split_suggest_term(stream.key()) else {
+                    continue;
+                };
+                if doc_level <= level {
+                    *counts.entry(term.to_string()).or_default() +=
+                        stream.value().doc_freq as usize;
+                    continue;
+                }
+                if !granted_docs.iter().any(|&(_, l)| l == doc_level) {
+                    continue;
+                }
+                let mut postings = inverted_index
+                    .read_postings_from_terminfo(stream.value(), IndexRecordOption::Basic)?;
+                let mut found = 0;
+                for &(doc_id, _) in granted_docs.iter().filter(|&&(_, l)| l == doc_level) {
+                    if postings.doc() <= doc_id && postings.seek(doc_id) == doc_id {
+                        found += 1;
+                    }
+                }
+                if found > 0 {
+                    *counts.entry(term.to_string()).or_default() += found;
+                }
+            }
+        }
+        let mut res: Vec<TermSuggestion> = counts
+            .into_iter()
+            .map(|(term, count)| TermSuggestion { term, count })
+            .collect();
+        res.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+        res.truncate(limit);
+        Ok(res)
+    }
+
+    /// 与某个文档相似的文档（MoreLikeThis），不含它自己，只返回level不超过用户level的和授权的，
+    /// 文档不在索引中时返回None
+    pub fn similar(
+        &self,
+        id: u64,
+        level: u8,
+        granted: &[u64],
+        limit: usize,
+    ) -> anyhow::Result<Option<Vec<SearchHit>>> {
+        let fields = self.fields;
+        let searcher = self.handle().reader.searcher();
+
+        let id_term = Term::from_field_u64(fields.id, id);
+        let id_query = TermQuery::new(id_term, IndexRecordOption::Basic);
+        let found = searcher.search(&id_query, &TopDocs::with_limit(1))?;
+        let Some(&(_, doc_add)) = found.first() else {
+            return Ok(None);
+        };
+
+        // 只用存储的标题和正文中的词
         let doc: Document = searcher.doc(doc_add)?;
-        let v = doc.get_first(fields.id).unwrap().as_u64().unwrap();
-        res.push((v, score));
+        let doc_fields: Vec<(Field, Vec<Value>)> = [fields.title, fields.body]
+            .into_iter()
+            .map(|field| (field, doc.get_all(field).cloned().collect()))
+            .collect();
+        let mlt_query = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(1)
+            .with_max_doc_frequency(searcher.num_docs().max(1))
+            .with_min_term_frequency(1)
+            .with_max_query_terms(MLT_MAX_QUERY_TERMS)
+            .with_min_word_length(1)
+            .with_document_fields(doc_fields);
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, Box::new(mlt_query) as Box<dyn Query>),
+            (Occur::MustNot, Box::new(id_query)),
+        ]);
+        let (query, max_level) = self.restrict_to_visible(Box::new(query), level, granted);
+
+        let filter = FilterCollector::new(
+            fields.level,
+            move |v: u64| v <= max_level as u64,
+            TopDocs::with_limit(limit.max(1)),
+        );
+        let mut res = Vec::new();
+        for (score, doc_add) in searcher.search(&query, &filter)? {
+            res.push(SearchHit {
+                id: read_id(&searcher, doc_add)?,
+                score,
+                highlight: None,
+            });
+        }
+        Ok(Some(res))
+    }
+
+    pub async fn add_doc(&self, txt: &txt::Model, body: String) -> anyhow::Result<()> {
+        self.add_doc_with_meta(txt, &DocMeta::default(), body).await
+    }
+
+    /// 构造索引文档，标题和正文用补全词的分词器再分一次词
+    fn to_doc(&self, txt: &txt::Model, meta: &DocMeta, body: String) -> Document {
+        let mut terms: BTreeSet<String> = BTreeSet::new();
+        terms.extend(self.analyze_with(SUGGEST_TOKENIZER, &txt.title));
+        terms.extend(self.analyze_with(SUGGEST_TOKENIZER, &body));
+        self.fields.to_doc(txt, meta, body, &terms)
+    }
+
+    pub async fn add_doc_with_meta(
+        &self,
+        txt: &txt::Model,
+        meta: &DocMeta,
+        body: String,
+    ) -> anyhow::Result<()> {
+        let doc = self.to_doc(txt, meta, body);
+        let mut pending_ops = self.pending_ops.lock().await;
+        self.handle().writer.read().await.add_document(doc.clone())?;
+        // 正在重建时记录下来
+        if let Some(ops) = pending_ops.as_mut() {
+            ops.push(IndexOp::Add(txt.id, doc));
+        }
+        Ok(())
+    }
+
+    pub async fn delete_doc(&self, id: u64) -> anyhow::Result<()> {
+        let term = Term::from_field_u64(self.fields.id, id);
+        let mut pending_ops = self.pending_ops.lock().await;
+        self.handle().writer.read().await.delete_term(term);
+        if let Some(ops) = pending_ops.as_mut() {
+            ops.push(IndexOp::Delete(id));
+        }
+        Ok(())
     }
-    Ok(res)
 }
 
-pub async fn add_doc_to_index(id: u64, title: String, body: String, level: u8) {
-    let fields = get_fields();
-    let doc = doc!(
-        fields.id => id,
-        fields.title => title,
-        fields.body => body,
-        fields.level => level as u64
-    );
-    let writer = get_writer();
-    let writer = writer.read().await;
-    let _ = writer.add_document(doc);
+/// 各个标签的命中数，多的在前
+fn count_tags(tag_counts: &FacetCounts) -> Vec<TagCount> {
+    let mut res: Vec<TagCount> = tag_counts
+        .get(Facet::root())
+        .filter_map(|(facet, count)| {
+            let tag = facet.to_path().last()?.to_string();
+            Some(TagCount { tag, count })
+        })
+        .collect();
+    res.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    res
 }
 
-pub async fn delete_from_index(id: u64) -> anyhow::Result<()> {
-    let term = Term::from_field_u64(get_fields().id, id);
-    let writer = get_writer();
-    let writer = writer.read().await;
+/// 从快速域读取文档id
+fn read_id(searcher: &Searcher, doc_add: DocAddress) -> anyhow::Result<u64> {
+    Ok(searcher
+        .segment_reader(doc_add.segment_ord)
+        .fast_fields()
+        .u64("id")?
+        .first_or_default_col(0)
+        .get_val(doc_add.doc_id))
+}
 
-    let _ = writer.delete_term(term);
+fn render_snippet(
+    generator: &SnippetGenerator,
+    doc: &Document,
+    options: &SnippetOptions,
+) -> Option<String> {
+    let mut snippet = generator.snippet_from_doc(doc);
+    if snippet.is_empty() {
+        return None;
+    }
+    snippet.set_snippet_prefix_postfix(&options.pre_tag, &options.post_tag);
+    Some(snippet.to_html())
+}
 
-    Ok(())
+#[derive(Clone, Copy, Debug, Default)]
+pub enum SearchField {
+    Title,
+    Body,
+    #[default]
+    All,
+    // 用拼音或拼音首字母检索
+    Pinyin,
+}
+
+impl From<String> for SearchField {
+    fn from(value: String) -> Self {
+        if value.eq_ignore_ascii_case("title") {
+            SearchField::Title
+        } else if value.eq_ignore_ascii_case("body") {
+            SearchField::Body
+        } else if value.eq_ignore_ascii_case("pinyin") {
+            SearchField::Pinyin
+        } else {
+            SearchField::All
+        }
+    }
+}
+
+impl SearchField {
+    pub fn fields(self, fields: &Fields) -> Vec<Field> {
+        match self {
+            SearchField::Title => vec![fields.title],
+            SearchField::Body => vec![fields.body],
+            SearchField::All => vec![fields.body, fields.title],
+            SearchField::Pinyin => vec![fields.pinyin],
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::env;
+
+use dotenv::dotenv;
+use sea_orm::DatabaseConnection;
+
+use crate::entities::txt;
+
+use super::{
+    mutation::{skip_txt_simhash, update_txt_simhash},
+    query::{
+        get_all_simhashes, get_simhashes_by_bands, get_txt_by_ids, get_txt_without_simhash,
+        read_file,
+    },
+};
+
+/// 指纹的汉明距离不超过它时视为近似重复
+pub const NEAR_DUPLICATE_DISTANCE: u32 = 3;
+/// 指纹分成的段数，比NEAR_DUPLICATE_DISTANCE多一段，
+/// 近似重复的两个指纹至少有一段完全相同，只需要比较有相同段的指纹
+pub const SIMHASH_BANDS: usize = NEAR_DUPLICATE_DISTANCE as usize + 1;
+const BAND_BITS: usize = 64 / SIMHASH_BANDS;
+// 每个分片的字数
+const SHINGLE_SIZE: usize = 3;
+
+/// 上传近似重复的文档时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NearDuplicatePolicy {
+    // 正常保存，返回中列出近似的文档
+    Warn,
+    // 拒绝保存
+    Reject,
+}
+
+/// 由环境变量NEAR_DUPLICATE_POLICY设置，reject时拒绝，默认只提示
+pub fn find_near_duplicate_policy_from_env() -> NearDuplicatePolicy {
+    dotenv().ok();
+    match env::var("NEAR_DUPLICATE_POLICY") {
+        Ok(policy) if policy.eq_ignore_ascii_case("reject") => NearDuplicatePolicy::Reject,
+        _ => NearDuplicatePolicy::Warn,
+    }
+}
+
+/// FNV-1a，结果跨版本稳定，可以存入数据库
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// 计算文本的SimHash指纹：去掉空白和标点、转小写后按连续的几个字分片，
+/// 没有文字时返回None
+pub fn simhash(text: &str) -> Option<u64> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if chars.is_empty() {
+        return None;
+    }
+    let mut weights = [0i64; 64];
+    let mut shingle = String::new();
+    for window in chars.windows(SHINGLE_SIZE.min(chars.len())) {
+        shingle.clear();
+        shingle.extend(window);
+        let hash = fnv1a(shingle.as_bytes());
+        for (i, weight) in weights.iter_mut().enumerate() {
+            if hash >> i & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    let fingerprint = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |acc, (i, _)| acc | 1 << i);
+    Some(fingerprint)
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 指纹的各段，从低位开始
+pub fn simhash_bands(simhash: u64) -> [u16; SIMHASH_BANDS] {
+    let mut bands = [0; SIMHASH_BANDS];
+    for (i, band) in bands.iter_mut().enumerate() {
+        *band = (simhash >> (i * BAND_BITS)) as u16;
+    }
+    bands
+}
+
+/// 找出与指纹近似的文档，不含回收站中的
+pub async fn find_near_duplicates(
+    conn: &DatabaseConnection,
+    simhash: u64,
+) -> anyhow::Result<Vec<txt::Model>> {
+    let ids: Vec<u64> = get_simhashes_by_bands(conn, &simhash_bands(simhash))
+        .await?
+        .into_iter()
+        .filter(|(_, other)| hamming_distance(simhash, *other) <= NEAR_DUPLICATE_DISTANCE)
+        .map(|(id, _)| id)
+        .collect();
+    Ok(get_txt_by_ids(conn, &ids).await?)
+}
+
+/// 把近似重复的文档分组（近似关系传递），只返回多于一个文档的组
+pub async fn near_duplicate_clusters(
+    conn: &DatabaseConnection,
+) -> anyhow::Result<Vec<Vec<txt::Model>>> {
+    let simhashes = get_all_simhashes(conn).await?;
+
+    // 并查集
+    let mut parent: Vec<usize> = (0..simhashes.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    // 指纹相同的直接合并，不同的指纹按段分桶，只比较同一个桶中的
+    let mut distinct: HashMap<u64, usize> = HashMap::new();
+    let mut buckets: HashMap<(usize, u16), Vec<usize>> = HashMap::new();
+    for (i, (_, simhash)) in simhashes.iter().enumerate() {
+        if let Some(&first) = distinct.get(simhash) {
+            let (a, b) = (find(&mut parent, i), find(&mut parent, first));
+            parent[a] = b;
+            continue;
+        }
+        distinct.insert(*simhash, i);
+        for (band, value) in simhash_bands(*simhash).into_iter().enumerate() {
+            buckets.entry((band, value)).or_default().push(i);
+        }
+    }
+    for bucket in buckets.values() {
+        for (k, &i) in bucket.iter().enumerate() {
+            for &j in &bucket[k + 1..] {
+                if hamming_distance(simhashes[i].1, simhashes[j].1) <= NEAR_DUPLICATE_DISTANCE {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[a] = b;
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<u64>> = HashMap::new();
+    for (i, (id, _)) in simhashes.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(*id);
+    }
+
+    let mut clusters = Vec::new();
+    for ids in groups.into_values().filter(|ids| ids.len() > 1) {
+        let mut docs = get_txt_by_ids(conn, &ids).await?;
+        docs.sort_unstable_by_key(|doc| doc.id);
+        clusters.push(docs);
+    }
+    clusters.sort_unstable_by_key(|docs| docs[0].id);
+    Ok(clusters)
+}
+
+/// 为还没有指纹的文档（升级前上传的）计算指纹，
+/// 读取失败或没有文字的文档记录下来，之后启动时不再重试
+pub async fn backfill_simhash(conn: DatabaseConnection) {
+    let docs = match get_txt_without_simhash(&conn).await {
+        Ok(docs) => docs,
+        Err(e) => {
+            println!("-->> {:<12} -- {e:?}", "SIMHASH");
+            return;
+        }
+    };
+    let (mut count, mut skipped) = (0, 0);
+    for doc in docs {
+        let simhash = match read_file(doc.hash.clone()).await {
+            Ok(text) => simhash(&text),
+            Err(e) => {
+                println!("-->> {:<12} -- doc {} {e:?}", "SIMHASH", doc.id);
+                None
+            }
+        };
+        let Some(simhash) = simhash else {
+            println!("-->> {:<12} -- skip doc {}", "SIMHASH", doc.id);
+            skipped += 1;
+            if let Err(e) = skip_txt_simhash(&conn, doc.id).await {
+                println!("-->> {:<12} -- doc {} {e:?}", "SIMHASH", doc.id);
+            }
+            continue;
+        };
+        match update_txt_simhash(&conn, doc.id, simhash).await {
+            Ok(()) => count += 1,
+            Err(e) => println!("-->> {:<12} -- doc {} {e:?}", "SIMHASH", doc.id),
+        }
+    }
+    println!("-->> {:<12} -- backfill {count} docs, skipped {skipped}", "SIMHASH");
+}
@@ -0,0 +1,53 @@
+use sea_orm::DatabaseConnection;
+use tokio::fs::read_to_string;
+
+use super::{
+    mutation::add_stopwords,
+    query::{count_stopwords, get_all_stopwords},
+    search::SearchService,
+};
+
+// 停用词表为空时用于初始化的文件
+const STOPWORD_SEED_PATH: &str = "resource/stopword.txt";
+// 与数据库中word列的长度一致
+const STOPWORD_MAX_LEN: usize = 64;
+
+/// 把以空白分隔的文本整理为停用词：转为小写，去掉空的、过长的和重复的
+pub fn parse_stopwords(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .filter(|word| word.chars().count() <= STOPWORD_MAX_LEN)
+        .collect();
+    words.sort_unstable();
+    words.dedup();
+    words
+}
+
+/// 停用词表为空时，从资源文件导入初始停用词，文件不存在时跳过
+pub async fn seed_stopwords(conn: &DatabaseConnection) -> anyhow::Result<()> {
+    if count_stopwords(conn).await? > 0 {
+        return Ok(());
+    }
+    let text = match read_to_string(STOPWORD_SEED_PATH).await {
+        Ok(text) => text,
+        Err(e) => {
+            println!("-->> {:<12} -- skip seeding, {STOPWORD_SEED_PATH}: {e}", "STOPWORD");
+            return Ok(());
+        }
+    };
+    let count = add_stopwords(conn, &parse_stopwords(&text)).await?;
+    println!("-->> {:<12} -- seeded {count} stopwords", "STOPWORD");
+    Ok(())
+}
+
+/// 从数据库加载停用词到分词器
+pub async fn load_stopwords(
+    conn: &DatabaseConnection,
+    search: &SearchService,
+) -> anyhow::Result<()> {
+    let stopwords = get_all_stopwords(conn).await?;
+    println!("-->> {:<12} -- have {} stopwords", "STOPWORD", stopwords.len());
+    search.set_stopwords(stopwords);
+    Ok(())
+}
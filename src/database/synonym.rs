@@ -0,0 +1,17 @@
+use sea_orm::DatabaseConnection;
+
+use super::{query::get_all_synonyms, search::SearchService};
+
+// 与数据库中word、synonym列的长度一致
+pub const SYNONYM_MAX_LEN: usize = 64;
+
+/// 从数据库加载同义词到查询扩展的缓存
+pub async fn load_synonyms(
+    conn: &DatabaseConnection,
+    search: &SearchService,
+) -> anyhow::Result<()> {
+    let synonyms = get_all_synonyms(conn).await?;
+    println!("-->> {:<12} -- have {} synonyms", "SYNONYM", synonyms.len());
+    search.set_synonyms(&synonyms);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::env;
+
+use chrono::{Duration, Utc};
+use dotenv::dotenv;
+use sea_orm::DatabaseConnection;
+use tokio::time::{self, sleep};
+
+use crate::entities::txt;
+
+use super::{
+    mutation::{delete_file, delete_txt_info},
+    query::{get_trashed_txt_before, get_versions_by_txt_id},
+};
+
+const TRASH_RETENTION_DEFAULT_DAYS: i64 = 30;
+
+/// 回收站保留天数，由环境变量TRASH_RETENTION_DAYS设置，默认30天
+pub fn find_trash_retention_from_env() -> i64 {
+    dotenv().ok();
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(TRASH_RETENTION_DEFAULT_DAYS)
+}
+
+/// 彻底删除文档：原文件、历史版本文件和数据库记录
+pub async fn purge_txt(conn: &DatabaseConnection, doc: txt::Model) -> anyhow::Result<()> {
+    for version in get_versions_by_txt_id(conn, doc.id).await? {
+        delete_file(&version.hash).await?;
+    }
+    delete_file(&doc.hash).await?;
+    println!("-->> {:<12} -- purge doc {} {:?}", "PURGE", doc.id, doc.title);
+    delete_txt_info(conn, doc).await?;
+    Ok(())
+}
+
+/// 彻底删除超过保留期的文档，返回删除的数量；
+/// 某个文档删除失败时记录下来并继续删除其余文档
+pub async fn purge_expired_trash(
+    conn: &DatabaseConnection,
+    retention_days: i64,
+) -> anyhow::Result<usize> {
+    let before = Utc::now() - Duration::days(retention_days);
+    let docs = get_trashed_txt_before(conn, before).await?;
+    let mut count = 0;
+    for doc in docs {
+        let doc_id = doc.id;
+        match purge_txt(conn, doc).await {
+            Ok(()) => count += 1,
+            Err(e) => println!("-->> {:<12} -- purge doc {doc_id} {e:?}", "PURGING"),
+        }
+    }
+    Ok(count)
+}
+
+/// 定时清理回收站
+pub async fn purging(conn: DatabaseConnection) {
+    let retention_days = find_trash_retention_from_env();
+    println!("-->> {:<12} -- keep trash for {retention_days} days", "PURGING");
+    loop {
+        match purge_expired_trash(&conn, retention_days).await {
+            Ok(0) => (),
+            Ok(count) => println!("-->> {:<12} -- purged {count} docs", "PURGING"),
+            Err(e) => println!("-->> {:<12} -- {e:?}", "PURGING"),
+        }
+        sleep(time::Duration::from_secs(60 * 60)).await;
+    }
+}
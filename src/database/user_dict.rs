@@ -0,0 +1,17 @@
+use sea_orm::DatabaseConnection;
+
+use super::{query::get_all_user_words, search::SearchService};
+
+// 与数据库中word列的长度一致
+pub const USER_WORD_MAX_LEN: usize = 64;
+
+/// 从数据库加载用户词典到分词器
+pub async fn load_user_dict(
+    conn: &DatabaseConnection,
+    search: &SearchService,
+) -> anyhow::Result<()> {
+    let words = get_all_user_words(conn).await?;
+    println!("-->> {:<12} -- have {} user words", "USER_DICT", words.len());
+    search.set_user_dict(&words);
+    Ok(())
+}
@@ -2,5 +2,15 @@
 
 pub mod prelude;
 
+pub mod folder;
+pub mod stopword;
+pub mod synonym;
+pub mod tag;
 pub mod txt;
+pub mod txt_grant;
+pub mod txt_tag;
+pub mod txt_version;
 pub mod user;
+pub mod user_dict;
+pub mod user_group;
+pub mod user_group_member;
@@ -1,4 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+pub use super::folder::Entity as Folder;
+pub use super::stopword::Entity as Stopword;
+pub use super::synonym::Entity as Synonym;
+pub use super::tag::Entity as Tag;
 pub use super::txt::Entity as Txt;
+pub use super::txt_grant::Entity as TxtGrant;
+pub use super::txt_tag::Entity as TxtTag;
+pub use super::txt_version::Entity as TxtVersion;
 pub use super::user::Entity as User;
+pub use super::user_dict::Entity as UserDict;
+pub use super::user_group::Entity as UserGroup;
+pub use super::user_group_member::Entity as UserGroupMember;
@@ -13,10 +13,30 @@ pub struct Model {
     pub hash: String,
     pub user_id: u64,
     pub level: u8,
+    pub format: String,
+    pub encoding: Option<String>,
+    pub deleted_at: Option<DateTimeUtc>,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+    pub size_bytes: u64,
+    pub char_count: u64,
+    #[serde(skip_serializing)]
+    pub simhash: Option<u64>,
+    pub folder_id: Option<u64>,
+    #[serde(skip_serializing)]
+    pub simhash_skipped: i8,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::folder::Entity",
+        from = "Column::FolderId",
+        to = "super::folder::Column::Id",
+        on_update = "Restrict",
+        on_delete = "Restrict"
+    )]
+    Folder,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -25,6 +45,36 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     User,
+    #[sea_orm(has_many = "super::txt_grant::Entity")]
+    TxtGrant,
+    #[sea_orm(has_many = "super::txt_tag::Entity")]
+    TxtTag,
+    #[sea_orm(has_many = "super::txt_version::Entity")]
+    TxtVersion,
+}
+
+impl Related<super::folder::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Folder.def()
+    }
+}
+
+impl Related<super::txt_grant::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TxtGrant.def()
+    }
+}
+
+impl Related<super::txt_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TxtTag.def()
+    }
+}
+
+impl Related<super::txt_version::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TxtVersion.def()
+    }
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -17,8 +17,20 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::folder::Entity")]
+    Folder,
     #[sea_orm(has_many = "super::txt::Entity")]
     Txt,
+    #[sea_orm(has_many = "super::txt_grant::Entity")]
+    TxtGrant,
+    #[sea_orm(has_many = "super::user_group_member::Entity")]
+    UserGroupMember,
+}
+
+impl Related<super::folder::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Folder.def()
+    }
 }
 
 impl Related<super::txt::Entity> for Entity {
@@ -27,4 +39,16 @@ impl Related<super::txt::Entity> for Entity {
     }
 }
 
+impl Related<super::txt_grant::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TxtGrant.def()
+    }
+}
+
+impl Related<super::user_group_member::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserGroupMember.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,3 +1,4 @@
+use database::search::SearchService;
 use sea_orm::DatabaseConnection;
 use serde::Serialize;
 
@@ -7,7 +8,8 @@ pub mod web;
 
 #[derive(Clone, Debug)]
 pub struct AppState {
-    pub conn: DatabaseConnection
+    pub conn: DatabaseConnection,
+    pub search: SearchService,
 }
 
 #[derive(Serialize)]
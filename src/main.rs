@@ -1,19 +1,25 @@
 extern crate tantivy;
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use ks_backend::{
     database::{
         db::*,
         init_datadir,
-        search::{commiting, init_index, rebuild_search_index},
+        metadata::backfill_metadata,
+        search::{SearchService, INDEXDIR},
+        simhash::backfill_simhash,
+        stopword::{load_stopwords, seed_stopwords},
+        synonym::load_synonyms,
+        trash::purging,
+        user_dict::load_user_dict,
     },
     web::{
-        login,
+        duplicate, folder, grant, group, login, stopword, synonym, tag, trash,
         txt::{self, download_api},
-        user,
+        user, user_dict, version,
     },
     AppState, Msg,
 };
@@ -23,7 +29,7 @@ use tower_http::limit::RequestBodyLimitLayer;
 #[tokio::main]
 async fn main() {
     // 初始化索引
-    let jh_init_index = tokio::spawn(init_index());
+    let jh_init_index = tokio::spawn(SearchService::open(INDEXDIR));
     // 初始化文件存储
     init_datadir().await.unwrap();
 
@@ -37,11 +43,40 @@ async fn main() {
 
     // 初始化admin
     let jh_admin_index = tokio::spawn(init_admin_user(conn.clone()));
-    // 建立索引
-    let _ = jh_init_index.await.unwrap();
-    let jh_build_index = tokio::spawn(rebuild_search_index(conn.clone()));
+    // 索引与数据库不一致时才重建索引
+    let search = jh_init_index.await.unwrap().unwrap();
+    // 停用词存在数据库中，表为空时从资源文件导入
+    seed_stopwords(&conn).await.unwrap();
+    load_stopwords(&conn, &search).await.unwrap();
+    load_user_dict(&conn, &search).await.unwrap();
+    load_synonyms(&conn, &search).await.unwrap();
+    // 为升级前上传的文档补上大小和时间，补上后索引中的需要更新
+    let backfilled = backfill_metadata(&conn).await.unwrap_or_else(|e| {
+        println!("-->> {:<12} -- {e:?}", "METADATA");
+        0
+    });
+    let need_rebuild = backfilled > 0 || search.out_of_sync(&conn).await.unwrap_or(true);
+    let jh_build_index = tokio::spawn({
+        let conn = conn.clone();
+        let search = search.clone();
+        async move {
+            if need_rebuild {
+                if let Err(e) = search.rebuild(&conn).await {
+                    println!("-->> {:<12} -- {e:?}", "REBUILD_INDEX");
+                }
+            }
+        }
+    });
 
-    let state = AppState { conn };
+    // 定时清理回收站
+    tokio::spawn(purging(conn.clone()));
+    // 为升级前上传的文档计算指纹
+    tokio::spawn(backfill_simhash(conn.clone()));
+
+    let state = AppState {
+        conn,
+        search: search.clone(),
+    };
 
     let app = Router::new()
         .route("/", get(root))
@@ -55,10 +90,76 @@ async fn main() {
                 .put(txt::update_doc_api),
         )
         .route("/doc/multi-upload", post(txt::upload_docs_api))
+        .route("/doc/:id/similar", get(txt::similar_docs_api))
+        .route("/duplicate", get(duplicate::near_duplicates_api))
+        .route("/tag", get(tag::tags_info_api))
+        .route(
+            "/doc/:id/tag",
+            get(tag::doc_tags_api).post(tag::add_doc_tags_api),
+        )
+        .route("/doc/:id/tag/:name", delete(tag::remove_doc_tag_api))
+        .route("/doc/:id/folder", put(folder::move_doc_api))
+        .route(
+            "/doc/:id/grant",
+            get(grant::grants_info_api).post(grant::add_grant_api),
+        )
+        .route("/doc/:id/grant/:gid", delete(grant::delete_grant_api))
+        .route(
+            "/group",
+            get(group::groups_info_api).post(group::add_group_api),
+        )
+        .route("/group/:id", delete(group::delete_group_api))
+        .route("/group/:id/member", get(group::group_members_api))
+        .route(
+            "/group/:id/member/:uid",
+            put(group::add_group_member_api).delete(group::remove_group_member_api),
+        )
+        .route(
+            "/folder",
+            get(folder::root_folder_api).post(folder::add_folder_api),
+        )
+        .route(
+            "/folder/:id",
+            get(folder::folder_info_api)
+                .put(folder::update_folder_api)
+                .delete(folder::delete_folder_api),
+        )
+        .route(
+            "/doc/:id/version",
+            get(version::versions_info_api).post(version::upload_version_api),
+        )
+        .route(
+            "/doc/:id/version/:vid/download",
+            get(version::download_version_api),
+        )
+        .route(
+            "/doc/:id/version/:vid/restore",
+            post(version::restore_version_api),
+        )
         .route("/download/:hash", get(download_api))
+        .route("/query/suggest", get(txt::suggest_api))
         .route("/query/:hash", get(txt::doc_info_hash_api))
         .route("/query", get(txt::query_api))
         .route("/index", post(txt::rebuild_index_api))
+        .route(
+            "/stopword",
+            get(stopword::stopwords_info_api).post(stopword::add_stopwords_api),
+        )
+        .route("/stopword/import", post(stopword::import_stopwords_api))
+        .route("/stopword/:word", delete(stopword::delete_stopword_api))
+        .route(
+            "/dict",
+            get(user_dict::user_dict_info_api).post(user_dict::save_user_word_api),
+        )
+        .route("/dict/:word", delete(user_dict::delete_user_word_api))
+        .route(
+            "/synonym",
+            get(synonym::synonyms_info_api).post(synonym::add_synonyms_api),
+        )
+        .route("/synonym/:id", delete(synonym::delete_synonym_api))
+        .route("/trash", get(trash::trash_info_api))
+        .route("/trash/:id", delete(trash::purge_doc_api))
+        .route("/trash/:id/restore", post(trash::restore_doc_api))
         .route(
             "/user",
             get(user::users_info_api).post(user::add_user_info_api),
@@ -77,7 +178,7 @@ async fn main() {
 
     let _ = jh_build_index.await.unwrap();
     let _ = jh_admin_index.await.unwrap();
-    tokio::spawn(commiting());
+    tokio::spawn(search.commiting());
     let _ = sleep(time::Duration::from_millis(5000));
     println!("Listening on {addr}");
     axum::serve(listener, app).await.unwrap();
@@ -0,0 +1,21 @@
+use axum::extract::State;
+use axum::Json;
+
+use super::error::*;
+use super::login::Claims;
+use super::user::validate_admin;
+use crate::database::simhash::near_duplicate_clusters;
+use crate::entities::txt;
+use crate::AppState;
+
+/// 查看近似重复的文档分组，需要admin
+pub async fn near_duplicates_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<Vec<txt::Model>>>> {
+    validate_admin(&claims)?;
+    let clusters = near_duplicate_clusters(&state.conn)
+        .await
+        .map_err(|_| Error::InternalError)?;
+    Ok(Json(clusters))
+}
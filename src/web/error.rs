@@ -22,11 +22,13 @@ pub enum Error {
     EmptyFileName,
     UploadFail,
     DuplicateFile,
+    NearDuplicateFile,
     EmptyFile,
     UnsportFileType,
 
     // search
     NoSuchFile,
+    NoSuchVersion,
     ErrorSearchQuery,
 
     // user
@@ -38,6 +40,33 @@ pub enum Error {
     InvalidMoveUser,
     NotAllowDeleteYourSelf,
     InvalidLevel,
+
+    // stopword
+    NoSuchStopword,
+
+    // user dict
+    NoSuchUserWord,
+    InvalidUserWord,
+
+    // synonym
+    NoSuchSynonym,
+    InvalidSynonym,
+
+    // tag
+    NoSuchTag,
+    InvalidTag,
+
+    // folder
+    NoSuchFolder,
+    InvalidFolder,
+    InvalidMoveFolder,
+    FolderNotEmpty,
+
+    // grant
+    NoSuchGroup,
+    InvalidGroup,
+    NoSuchGrant,
+    InvalidGrant,
     //
     TODO,
 }
@@ -51,10 +80,12 @@ impl Display for Error {
             Error::EmptyFileName => "Empty Filename",
             Error::UploadFail => "Uplord Fail",
             Error::DuplicateFile => "Duplicate File",
+            Error::NearDuplicateFile => "Near Duplicate File",
             Error::EmptyFile => "Empty File",
             Error::TODO => "To Do",
             Error::UnsportFileType => "UnsportFileType",
             Error::NoSuchFile => "No Such File",
+            Error::NoSuchVersion => "No Such Version",
             Error::ErrorSearchQuery => "Error Search Query",
             Error::NoSuchUser => "No Such User",
             Error::DuplicateUserName => "Duplicate UserName",
@@ -64,6 +95,21 @@ impl Display for Error {
             Error::InvalidMoveUser => "Invalid Move User",
             Error::NotAllowDeleteYourSelf => "Not Allow Delete Yourself",
             Error::InvalidLevel => "Invalid Level",
+            Error::NoSuchStopword => "No Such Stopword",
+            Error::NoSuchUserWord => "No Such User Word",
+            Error::InvalidUserWord => "Invalid User Word",
+            Error::NoSuchSynonym => "No Such Synonym",
+            Error::InvalidSynonym => "Invalid Synonym",
+            Error::NoSuchTag => "No Such Tag",
+            Error::InvalidTag => "Invalid Tag",
+            Error::NoSuchFolder => "No Such Folder",
+            Error::InvalidFolder => "Invalid Folder",
+            Error::InvalidMoveFolder => "Invalid Move Folder",
+            Error::FolderNotEmpty => "Folder Not Empty",
+            Error::NoSuchGroup => "No Such Group",
+            Error::InvalidGroup => "Invalid Group",
+            Error::NoSuchGrant => "No Such Grant",
+            Error::InvalidGrant => "Invalid Grant",
         };
 
         write!(f, "{}", output)
@@ -75,7 +121,16 @@ impl From<Error> for StatusCode {
         match value {
             Error::LoginFail | Error::InvalidToken => StatusCode::UNAUTHORIZED,
             Error::InternalError | Error::TODO => StatusCode::INTERNAL_SERVER_ERROR,
-            Error::NoSuchFile | Error::NoSuchUser => StatusCode::NOT_FOUND,
+            Error::NoSuchFile
+            | Error::NoSuchVersion
+            | Error::NoSuchUser
+            | Error::NoSuchStopword
+            | Error::NoSuchUserWord
+            | Error::NoSuchSynonym
+            | Error::NoSuchTag
+            | Error::NoSuchFolder
+            | Error::NoSuchGroup
+            | Error::NoSuchGrant => StatusCode::NOT_FOUND,
             _ => StatusCode::NOT_ACCEPTABLE
         }
     }
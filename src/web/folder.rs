@@ -0,0 +1,247 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use super::error::*;
+use super::grant::owned_doc;
+use super::login::Claims;
+use super::txt::{reindex_doc, reindex_docs};
+use super::user::validate_admin;
+use crate::database::mutation::{add_folder, delete_folder, move_txt_to_folder, update_folder};
+use crate::database::query::{
+    folder_is_empty, get_folder_by_id, get_folder_subtree_ids, get_folders_by_parent,
+    get_granted_txt_ids, get_txt_by_folder_id, get_txt_in_folders,
+};
+use crate::entities::{folder, txt};
+use crate::{AppState, Msg};
+
+// 与folder表name列的长度一致
+pub const FOLDER_NAME_MAX_LEN: usize = 64;
+
+fn valid_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > FOLDER_NAME_MAX_LEN {
+        return Err(Error::InvalidFolder);
+    }
+    Ok(name.to_string())
+}
+
+/// 取出调用者拥有的文件夹
+async fn owned_folder(state: &AppState, claims: &Claims, id: u64) -> Result<folder::Model> {
+    match get_folder_by_id(&state.conn, id).await? {
+        Some(folder) if folder.user_id == claims.id => Ok(folder),
+        _ => Err(Error::NoSuchFolder),
+    }
+}
+
+/// 取出调用者可以查看的文件夹：文件夹是私有的，需要是所有者或admin
+pub(crate) async fn viewable_folder(
+    state: &AppState,
+    claims: &Claims,
+    id: u64,
+) -> Result<folder::Model> {
+    let is_admin = validate_admin(claims).is_ok();
+    match get_folder_by_id(&state.conn, id).await? {
+        Some(folder) if folder.user_id == claims.id || is_admin => Ok(folder),
+        _ => Err(Error::NoSuchFolder),
+    }
+}
+
+/// 移动的目标，0表示顶层，否则需要是调用者的文件夹
+async fn target_folder(state: &AppState, claims: &Claims, id: u64) -> Result<Option<u64>> {
+    if id == 0 {
+        return Ok(None);
+    }
+    match get_folder_by_id(&state.conn, id).await? {
+        Some(folder) if folder.user_id == claims.id => Ok(Some(folder.id)),
+        _ => Err(Error::InvalidMoveFolder),
+    }
+}
+
+/// 文件夹的内容，folder为None时为调用者的顶层
+#[derive(Clone, Debug, Serialize)]
+pub struct FolderContent {
+    folder: Option<folder::Model>,
+    folders: Vec<folder::Model>,
+    docs: Vec<txt::Model>,
+}
+
+async fn folder_content(
+    state: &AppState,
+    claims: &Claims,
+    folder: Option<folder::Model>,
+) -> Result<FolderContent> {
+    let folder_id = folder.as_ref().map(|folder| folder.id);
+    // 文件夹是私有的，只列出文件夹所有者的子文件夹和文档
+    let owner = folder.as_ref().map_or(claims.id, |folder| folder.user_id);
+    let granted = get_granted_txt_ids(&state.conn, claims.id, false).await?;
+    Ok(FolderContent {
+        folders: get_folders_by_parent(&state.conn, folder_id, owner).await?,
+        docs: get_txt_by_folder_id(&state.conn, folder_id, owner, claims.level, &granted).await?,
+        folder,
+    })
+}
+
+/// 查看自己顶层的文件夹和不在文件夹中的文档
+pub async fn root_folder_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<FolderContent>> {
+    Ok(Json(folder_content(&state, &claims, None).await?))
+}
+
+/// 查看文件夹中的子文件夹和文档，需要是文件夹的所有者或admin，
+/// 文档只包括用户可以查看的
+pub async fn folder_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<u64>,
+) -> Result<Json<FolderContent>> {
+    let folder = viewable_folder(&state, &claims, id).await?;
+    Ok(Json(folder_content(&state, &claims, Some(folder)).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewFolderArg {
+    name: String,
+    // 为空时在顶层
+    parent: Option<u64>,
+    // 为空时与上级文件夹相同，在顶层时为用户的level
+    level: Option<u8>,
+}
+
+/// 新建文件夹，上级文件夹需要是调用者的
+pub async fn add_folder_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<NewFolderArg>,
+) -> Result<Json<folder::Model>> {
+    let name = valid_name(&payload.name)?;
+    let parent = match payload.parent {
+        Some(id) => Some(owned_folder(&state, &claims, id).await?),
+        None => None,
+    };
+    let level = payload
+        .level
+        .or(parent.as_ref().map(|parent| parent.level))
+        .unwrap_or(claims.level);
+    if level > claims.level {
+        return Err(Error::InvalidLevel);
+    }
+    let id = add_folder(
+        &state.conn,
+        &name,
+        claims.id,
+        parent.map(|parent| parent.id),
+        level,
+    )
+    .await?;
+    println!("-->> {:<12} -- add folder {name:?}", "FOLDER");
+    let folder = get_folder_by_id(&state.conn, id)
+        .await?
+        .ok_or(Error::InternalError)?;
+    Ok(Json(folder))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFolderArg {
+    name: Option<String>,
+    // 移到这个文件夹中，0表示顶层
+    parent: Option<u64>,
+    level: Option<u8>,
+}
+
+/// 修改文件夹，需要是文件夹的所有者，不能移到自己或自己的子文件夹中
+pub async fn update_folder_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<u64>,
+    Json(payload): Json<UpdateFolderArg>,
+) -> Result<Json<folder::Model>> {
+    let folder = owned_folder(&state, &claims, id).await?;
+    let name = match &payload.name {
+        Some(name) => Some(valid_name(name)?),
+        None => None,
+    };
+    if matches!(payload.level, Some(level) if level > claims.level) {
+        return Err(Error::InvalidLevel);
+    }
+    let subtree = get_folder_subtree_ids(&state.conn, folder.id).await?;
+    let parent = match payload.parent {
+        Some(parent) => {
+            let parent = target_folder(&state, &claims, parent).await?;
+            if matches!(parent, Some(parent) if subtree.contains(&parent)) {
+                return Err(Error::InvalidMoveFolder);
+            }
+            Some(parent)
+        }
+        None => None,
+    };
+    let moved = matches!(parent, Some(parent) if parent != folder.parent_id);
+
+    let folder = update_folder(&state.conn, folder, name, parent, payload.level).await?;
+    if moved {
+        println!("-->> {:<12} -- move folder {}", "FOLDER", folder.id);
+        let docs = get_txt_in_folders(&state.conn, &subtree).await?;
+        reindex_docs(&state, &docs).await?;
+    }
+    Ok(Json(folder))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteFolderArg {
+    // 文件夹不为空时，内容移到这个文件夹中，0表示顶层
+    to: Option<u64>,
+}
+
+/// 删除文件夹，需要是文件夹的所有者
+///
+/// 文件夹中有文档或子文件夹时需要提供to，
+/// to不能是这个文件夹或其子文件夹
+pub async fn delete_folder_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(delete_folder_arg): Query<DeleteFolderArg>,
+    Path(id): Path<u64>,
+) -> Result<Json<Msg>> {
+    let folder = owned_folder(&state, &claims, id).await?;
+    // 若为空
+    if folder_is_empty(&state.conn, folder.id).await? {
+        delete_folder(&state.conn, folder, None).await?;
+        return Ok(Json(Msg::from("Ok")));
+    }
+    // 不为空，需要提供to
+    let Some(to) = delete_folder_arg.to else {
+        return Err(Error::FolderNotEmpty);
+    };
+    let move_to = target_folder(&state, &claims, to).await?;
+    let subtree = get_folder_subtree_ids(&state.conn, folder.id).await?;
+    if matches!(move_to, Some(move_to) if subtree.contains(&move_to)) {
+        return Err(Error::InvalidMoveFolder);
+    }
+    let docs = get_txt_in_folders(&state.conn, &subtree).await?;
+    println!("-->> {:<12} -- delete folder {}", "FOLDER", folder.id);
+    delete_folder(&state.conn, folder, move_to).await?;
+    reindex_docs(&state, &docs).await?;
+    Ok(Json(Msg::from("Ok")))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveDocArg {
+    // 0表示顶层
+    folder: u64,
+}
+
+/// 将文档移到自己的文件夹中，需要是文档的所有者
+pub async fn move_doc_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+    Json(payload): Json<MoveDocArg>,
+) -> Result<Json<txt::Model>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    let folder_id = target_folder(&state, &claims, payload.folder).await?;
+    let doc = move_txt_to_folder(&state.conn, doc, folder_id).await?;
+    reindex_doc(&state, &doc).await?;
+    Ok(Json(doc))
+}
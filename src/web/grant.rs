@@ -0,0 +1,133 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use chrono::Utc;
+use sea_orm::{prelude::DateTimeUtc, DatabaseConnection};
+use serde::Deserialize;
+
+use super::error::*;
+use super::login::Claims;
+use crate::database::mutation::{add_grant, delete_grant, NewGrant};
+use crate::database::query::{
+    get_grant_by_id, get_grants_by_txt_id, get_group_by_id, get_txt_by_id, get_user_by_id,
+    has_grant,
+};
+use crate::entities::{txt, txt_grant};
+use crate::{AppState, Msg};
+
+/// 用户是文档的所有者，且文档的level不超过用户当前的level；
+/// 所有者的level降低后，与其他用户一样不能查看、修改和管理level更高的文档
+pub(crate) fn owns_visible(doc: &txt::Model, claims: &Claims) -> bool {
+    doc.user_id == claims.id && doc.level <= claims.level
+}
+
+/// 用户可以查看的文档：level不超过用户level，或者有未过期的授权，
+/// 与搜索和列表中的可见范围一致，所有者也不例外
+pub async fn visible_doc(
+    conn: &DatabaseConnection,
+    claims: &Claims,
+    doc: Option<txt::Model>,
+) -> Result<txt::Model> {
+    match doc {
+        Some(doc) if doc.level <= claims.level => Ok(doc),
+        Some(doc) if has_grant(conn, doc.id, claims.id, false).await? => Ok(doc),
+        _ => Err(Error::NoSuchFile),
+    }
+}
+
+/// 用户可以修改和删除的文档：是文档的所有者且可以查看，或者有未过期的可修改的授权
+pub async fn writable_doc(
+    conn: &DatabaseConnection,
+    claims: &Claims,
+    doc: Option<txt::Model>,
+) -> Result<txt::Model> {
+    match doc {
+        Some(doc) if owns_visible(&doc, claims) => Ok(doc),
+        Some(doc) if has_grant(conn, doc.id, claims.id, true).await? => Ok(doc),
+        _ => Err(Error::NoSuchFile),
+    }
+}
+
+/// 取出调用者拥有且可以查看的文档，授权、标签和所在文件夹只有所有者可以管理
+pub async fn owned_doc(state: &AppState, claims: &Claims, doc_id: u64) -> Result<txt::Model> {
+    match get_txt_by_id(&state.conn, doc_id).await? {
+        Some(doc) if owns_visible(&doc, claims) => Ok(doc),
+        _ => Err(Error::NoSuchFile),
+    }
+}
+
+/// 查看文档的所有授权，需要是文档的所有者
+pub async fn grants_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+) -> Result<Json<Vec<txt_grant::Model>>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    Ok(Json(get_grants_by_txt_id(&state.conn, doc.id).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantArg {
+    // user和group有且只有一个
+    user: Option<u64>,
+    group: Option<u64>,
+    // 默认只读
+    write: Option<bool>,
+    // RFC 3339格式，为空时永不过期
+    expires_at: Option<DateTimeUtc>,
+}
+
+/// 授权用户或用户组访问文档，需要是文档的所有者
+pub async fn add_grant_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+    Json(payload): Json<GrantArg>,
+) -> Result<Json<txt_grant::Model>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    match (payload.user, payload.group) {
+        (Some(user_id), None) if user_id != claims.id => {
+            get_user_by_id(&state.conn, user_id)
+                .await?
+                .ok_or(Error::NoSuchUser)?;
+        }
+        (None, Some(group_id)) => {
+            get_group_by_id(&state.conn, group_id)
+                .await?
+                .ok_or(Error::NoSuchGroup)?;
+        }
+        _ => return Err(Error::InvalidGrant),
+    }
+    if matches!(payload.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return Err(Error::InvalidGrant);
+    }
+
+    let grant = NewGrant {
+        user_id: payload.user,
+        group_id: payload.group,
+        can_write: payload.write.unwrap_or(false),
+        expires_at: payload.expires_at,
+    };
+    let id = add_grant(&state.conn, doc.id, &grant).await?;
+    println!("-->> {:<12} -- add grant {id} of doc {}", "GRANT", doc.id);
+    let grant = get_grant_by_id(&state.conn, id)
+        .await?
+        .ok_or(Error::InternalError)?;
+    Ok(Json(grant))
+}
+
+/// 删除文档的授权，需要是文档的所有者
+pub async fn delete_grant_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((doc_id, grant_id)): Path<(u64, u64)>,
+) -> Result<Json<Msg>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    match get_grant_by_id(&state.conn, grant_id).await? {
+        Some(grant) if grant.txt_id == doc.id => {
+            delete_grant(&state.conn, grant.id).await?;
+            println!("-->> {:<12} -- delete grant {} of doc {}", "GRANT", grant.id, doc.id);
+            Ok(Json(Msg::from("Ok")))
+        }
+        _ => Err(Error::NoSuchGrant),
+    }
+}
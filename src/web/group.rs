@@ -0,0 +1,110 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use super::error::*;
+use super::login::Claims;
+use super::user::validate_admin;
+use crate::database::mutation::{add_group, add_group_member, delete_group, remove_group_member};
+use crate::database::query::{get_all_groups, get_group_by_id, get_group_members, get_user_by_id};
+use crate::entities::user_group;
+use crate::{AppState, Msg};
+
+// 与user_group表name列的长度一致
+pub const GROUP_NAME_MAX_LEN: usize = 32;
+
+#[derive(Debug, Deserialize)]
+pub struct GroupArg {
+    name: String,
+}
+
+async fn group_by_id(state: &AppState, id: u64) -> Result<user_group::Model> {
+    get_group_by_id(&state.conn, id)
+        .await?
+        .ok_or(Error::NoSuchGroup)
+}
+
+/// 查看所有用户组，需要admin
+pub async fn groups_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<user_group::Model>>> {
+    validate_admin(&claims)?;
+    Ok(Json(get_all_groups(&state.conn).await?))
+}
+
+/// 新建用户组，需要admin
+pub async fn add_group_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<GroupArg>,
+) -> Result<Json<user_group::Model>> {
+    validate_admin(&claims)?;
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > GROUP_NAME_MAX_LEN {
+        return Err(Error::InvalidGroup);
+    }
+    // 名称不能重复
+    if get_all_groups(&state.conn)
+        .await?
+        .iter()
+        .any(|group| group.name == name)
+    {
+        return Err(Error::InvalidGroup);
+    }
+    let id = add_group(&state.conn, name).await?;
+    println!("-->> {:<12} -- add group {name:?}", "GROUP");
+    Ok(Json(group_by_id(&state, id).await?))
+}
+
+/// 删除用户组，授权给它的授权一并删除，需要admin
+pub async fn delete_group_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<u64>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let group = group_by_id(&state, id).await?;
+    delete_group(&state.conn, group).await?;
+    Ok(Json(Msg::from("Ok")))
+}
+
+/// 查看用户组的成员id，需要admin
+pub async fn group_members_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<u64>>> {
+    validate_admin(&claims)?;
+    let group = group_by_id(&state, id).await?;
+    Ok(Json(get_group_members(&state.conn, group.id).await?))
+}
+
+/// 把用户加入用户组，需要admin
+pub async fn add_group_member_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((id, user_id)): Path<(u64, u64)>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let group = group_by_id(&state, id).await?;
+    let user = get_user_by_id(&state.conn, user_id)
+        .await?
+        .ok_or(Error::NoSuchUser)?;
+    let count = add_group_member(&state.conn, group.id, user.id).await?;
+    Ok(Json(Msg::from(format!("added {count}").as_str())))
+}
+
+/// 把用户移出用户组，需要admin
+pub async fn remove_group_member_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((id, user_id)): Path<(u64, u64)>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let group = group_by_id(&state, id).await?;
+    if remove_group_member(&state.conn, group.id, user_id).await? == 0 {
+        return Err(Error::NoSuchUser);
+    }
+    Ok(Json(Msg::from("Ok")))
+}
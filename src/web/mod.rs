@@ -1,4 +1,14 @@
+pub mod duplicate;
 pub mod error;
+pub mod folder;
+pub mod grant;
+pub mod group;
 pub mod login;
+pub mod stopword;
+pub mod synonym;
+pub mod tag;
+pub mod trash;
 pub mod txt;
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod user_dict;
+pub mod version;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use axum::extract::{Multipart, Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use super::error::*;
+use super::login::Claims;
+use super::user::validate_admin;
+use crate::database::extract::decode_text;
+use crate::database::mutation::{add_stopwords, delete_stopword};
+use crate::database::query::get_all_stopwords;
+use crate::database::stopword::{load_stopwords, parse_stopwords};
+use crate::{AppState, Msg};
+
+#[derive(Debug, Deserialize)]
+pub struct StopwordsArg {
+    words: Vec<String>,
+}
+
+/// 停用词修改后重新加载分词器，并在后台重建索引
+async fn reload_stopwords(state: &AppState) -> Result<()> {
+    load_stopwords(&state.conn, &state.search)
+        .await
+        .map_err(|_| Error::InternalError)?;
+    state.search.schedule_rebuild(state.conn.clone());
+    Ok(())
+}
+
+/// 添加停用词并在有变化时重建索引
+async fn add_words(state: &AppState, words: Vec<String>) -> Result<Json<Msg>> {
+    let count = add_stopwords(&state.conn, &words).await?;
+    println!("-->> {:<12} -- add {count} stopwords", "STOPWORD");
+    if count > 0 {
+        reload_stopwords(state).await?;
+    }
+    Ok(Json(Msg::from(format!("added {count}").as_str())))
+}
+
+/// 查看所有停用词，需要admin
+pub async fn stopwords_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<String>>> {
+    validate_admin(&claims)?;
+    Ok(Json(get_all_stopwords(&state.conn).await?))
+}
+
+/// 添加停用词，需要admin
+pub async fn add_stopwords_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<StopwordsArg>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let words = parse_stopwords(&payload.words.join(" "));
+    add_words(&state, words).await
+}
+
+/// 从文件导入停用词，以空白分隔，需要admin
+pub async fn import_stopwords_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    mut multipart: Multipart,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let field = multipart
+        .next_field()
+        .await
+        .map_err(|_| Error::UploadFail)?
+        .ok_or(Error::EmptyFile)?;
+    let data = field.bytes().await.map_err(|_| Error::UploadFail)?;
+    let (text, _) = decode_text(&data).map_err(|_| Error::UnsportFileType)?;
+    add_words(&state, parse_stopwords(&text)).await
+}
+
+/// 删除停用词，需要admin
+pub async fn delete_stopword_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(word): Path<String>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let count = delete_stopword(&state.conn, &word.to_lowercase()).await?;
+    if count == 0 {
+        return Err(Error::NoSuchStopword);
+    }
+    println!("-->> {:<12} -- delete stopword {word}", "STOPWORD");
+    reload_stopwords(&state).await?;
+    Ok(Json(Msg::from("Ok")))
+}
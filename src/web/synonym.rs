@@ -0,0 +1,87 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use super::error::*;
+use super::login::Claims;
+use super::user::validate_admin;
+use crate::database::mutation::{add_synonyms, delete_synonym};
+use crate::database::query::get_all_synonyms;
+use crate::database::synonym::{load_synonyms, SYNONYM_MAX_LEN};
+use crate::entities::synonym;
+use crate::{AppState, Msg};
+
+#[derive(Debug, Deserialize)]
+pub struct SynonymsArg {
+    word: String,
+    synonyms: Vec<String>,
+    // 是否同时添加反向的同义词，默认为true；缩写只需展开时设为false
+    bidirectional: Option<bool>,
+}
+
+/// 同义词修改后重新加载缓存，只影响查询，不需要重建索引
+async fn reload_synonyms(state: &AppState) -> Result<()> {
+    load_synonyms(&state.conn, &state.search)
+        .await
+        .map_err(|_| Error::InternalError)
+}
+
+fn valid_word(word: &str) -> bool {
+    !word.is_empty() && word.chars().count() <= SYNONYM_MAX_LEN
+}
+
+/// 查看所有同义词，需要admin
+pub async fn synonyms_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<synonym::Model>>> {
+    validate_admin(&claims)?;
+    Ok(Json(get_all_synonyms(&state.conn).await?))
+}
+
+/// 添加同义词，需要admin
+pub async fn add_synonyms_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<SynonymsArg>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let word = payload.word.trim().to_string();
+    let synonyms: Vec<String> = payload
+        .synonyms
+        .iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| *s != word)
+        .collect();
+    if !valid_word(&word) || synonyms.is_empty() || !synonyms.iter().all(|s| valid_word(s)) {
+        return Err(Error::InvalidSynonym);
+    }
+
+    let bidirectional = payload.bidirectional.unwrap_or(true);
+    let mut pairs = Vec::with_capacity(synonyms.len() * 2);
+    for synonym in synonyms {
+        if bidirectional {
+            pairs.push((synonym.clone(), word.clone()));
+        }
+        pairs.push((word.clone(), synonym));
+    }
+    let count = add_synonyms(&state.conn, &pairs).await?;
+    println!("-->> {:<12} -- add {count} synonyms of {word}", "SYNONYM");
+    reload_synonyms(&state).await?;
+    Ok(Json(Msg::from(format!("added {count}").as_str())))
+}
+
+/// 删除同义词，需要admin
+pub async fn delete_synonym_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<u64>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    if delete_synonym(&state.conn, id).await? == 0 {
+        return Err(Error::NoSuchSynonym);
+    }
+    println!("-->> {:<12} -- delete synonym {id}", "SYNONYM");
+    reload_synonyms(&state).await?;
+    Ok(Json(Msg::from("Ok")))
+}
@@ -0,0 +1,94 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use super::error::*;
+use super::grant::{owned_doc, visible_doc};
+use super::login::Claims;
+use super::txt::reindex_doc;
+use crate::database::mutation::{add_txt_tags, remove_txt_tag};
+use crate::database::query::{get_all_tags, get_tags_by_txt_id, get_txt_by_id};
+use crate::entities::tag;
+use crate::AppState;
+
+// 与tag表name列的长度一致
+pub const TAG_MAX_LEN: usize = 32;
+
+#[derive(Debug, Deserialize)]
+pub struct TagsArg {
+    tags: Vec<String>,
+}
+
+/// 标签统一为小写，不能包含'/'，否则会被当作facet的层级；
+/// 保存标签和按标签过滤时都用它处理
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || tag.chars().count() > TAG_MAX_LEN || tag.contains('/') {
+        return Err(Error::InvalidTag);
+    }
+    Ok(tag)
+}
+
+/// 查看所有标签
+pub async fn tags_info_api(
+    State(state): State<AppState>,
+    _claims: Claims,
+) -> Result<Json<Vec<tag::Model>>> {
+    Ok(Json(get_all_tags(&state.conn).await?))
+}
+
+/// 查看文档的标签
+pub async fn doc_tags_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+) -> Result<Json<Vec<String>>> {
+    let doc = get_txt_by_id(&state.conn, doc_id).await?;
+    let doc = visible_doc(&state.conn, &claims, doc).await?;
+    Ok(Json(get_tags_by_txt_id(&state.conn, doc.id).await?))
+}
+
+/// 给文档添加标签，需要是文档的所有者，返回文档现在的标签
+pub async fn add_doc_tags_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+    Json(payload): Json<TagsArg>,
+) -> Result<Json<Vec<String>>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    let mut tags = payload
+        .tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .collect::<Result<Vec<String>>>()?;
+    if tags.is_empty() {
+        return Err(Error::InvalidTag);
+    }
+    tags.sort();
+    tags.dedup();
+
+    let count = add_txt_tags(&state.conn, doc.id, &tags).await?;
+    println!("-->> {:<12} -- add {count} tags to doc {}", "TAG", doc.id);
+    // 标签是索引的facet，需要重新索引文档
+    reindex_doc(&state, &doc).await?;
+    Ok(Json(get_tags_by_txt_id(&state.conn, doc.id).await?))
+}
+
+/// 去掉文档的标签，需要是文档的所有者，返回文档现在的标签
+pub async fn remove_doc_tag_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((doc_id, name)): Path<(u64, String)>,
+) -> Result<Json<Vec<String>>> {
+    let doc = owned_doc(&state, &claims, doc_id).await?;
+    let name = normalize_tag(&name).map_err(|_| Error::NoSuchTag)?;
+    if remove_txt_tag(&state.conn, doc.id, &name).await? == 0 {
+        return Err(Error::NoSuchTag);
+    }
+    println!(
+        "-->> {:<12} -- remove tag {name} from doc {}",
+        "TAG", doc.id
+    );
+    reindex_doc(&state, &doc).await?;
+    Ok(Json(get_tags_by_txt_id(&state.conn, doc.id).await?))
+}
@@ -0,0 +1,58 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use super::error::*;
+use super::grant::owns_visible;
+use super::login::Claims;
+use super::txt::reindex_doc;
+use super::user::validate_admin;
+use crate::database::mutation::restore_txt;
+use crate::database::query::{get_trashed_txt, get_trashed_txt_by_id};
+use crate::database::trash::purge_txt;
+use crate::entities::txt;
+use crate::{AppState, Msg};
+
+/// 查看回收站，admin可以看到所有用户的文档
+pub async fn trash_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<txt::Model>>> {
+    let user_id = match validate_admin(&claims) {
+        Ok(()) => None,
+        Err(_) => Some(claims.id),
+    };
+    let res = get_trashed_txt(&state.conn, user_id).await?;
+    Ok(Json(res))
+}
+
+/// 从回收站中恢复文档，需要是文档的所有者且可以查看
+pub async fn restore_doc_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+) -> Result<Json<txt::Model>> {
+    let doc = match get_trashed_txt_by_id(&state.conn, doc_id).await? {
+        Some(doc) if owns_visible(&doc, &claims) => doc,
+        _ => return Err(Error::NoSuchFile),
+    };
+    let doc = restore_txt(&state.conn, doc).await?;
+    // 重新加入索引
+    reindex_doc(&state, &doc).await?;
+    Ok(Json(doc))
+}
+
+/// 彻底删除回收站中的文档，需要admin
+pub async fn purge_doc_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let doc = get_trashed_txt_by_id(&state.conn, doc_id)
+        .await?
+        .ok_or(Error::NoSuchFile)?;
+    purge_txt(&state.conn, doc)
+        .await
+        .map_err(|_| Error::InternalError)?;
+    Ok(Json(Msg::from("Ok")))
+}
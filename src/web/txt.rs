@@ -1,91 +1,259 @@
 use std::cmp::min;
+use std::collections::HashMap;
 
+use axum::extract::multipart::Field;
 use axum::extract::{Multipart, Path, Query, State};
 use axum::http::{header, HeaderMap};
 use axum::Json;
 use data_encoding::HEXUPPER;
 use ring::digest::{Context, SHA256};
 
+use sea_orm::{prelude::DateTimeUtc, DatabaseConnection};
 use serde::{Deserialize, Serialize};
-use tantivy::doc;
 
 use urlencoding::{decode, encode};
 
 use super::error::*;
+use super::folder::viewable_folder;
+use super::grant::{visible_doc, writable_doc};
 use super::login::Claims;
+use super::tag::normalize_tag;
+use crate::database::extract::{extract_text, DocFormat};
 use crate::database::mutation::{
-    add_txt_info, delete_file, delete_txt_info, update_doc_info, write_file,
+    add_txt_info, move_txt_to_folder, trash_txt, update_doc_info, write_file, write_text_file,
+    TxtContent,
+};
+use crate::database::query::{
+    get_all_txt_lte_level, get_folder_by_id, get_folder_path, get_granted_txt_ids,
+    get_tags_by_txt_id, get_txt_by_hash, get_txt_by_id, get_txt_by_ids, hash_exists, read_file,
+    read_raw_file,
+};
+use crate::database::simhash::{
+    find_near_duplicate_policy_from_env, find_near_duplicates, simhash, NearDuplicatePolicy,
 };
-use crate::database::query::{get_all_txt_lte_level, get_txt_by_hash, get_txt_by_id, read_file};
 use crate::database::search::{
-    add_doc_to_index, delete_from_index, rebuild_search_index, search_from_rev_index, SearchField,
+    DocMeta, Highlight, SearchField, SearchFilter, SearchOptions, SnippetOptions, Sort,
+    Suggestions, TagCount,
 };
 use crate::Msg;
-use crate::{entities::txt, AppState};
+use crate::entities::{folder, txt};
+use crate::AppState;
+
+/// 检查文件并提取文本：非空、不与任何文档或历史版本重复，此时还不写入本地
+pub(crate) async fn check_file(
+    conn: &DatabaseConnection,
+    filename: &str,
+    data: &[u8],
+    hash_value: &str,
+) -> Result<(TxtContent, String)> {
+    // 空文件
+    if data.is_empty() {
+        println!("-->> {:<12} -- EMPTY_FILE", "STORE_FILE");
+        return Err(Error::EmptyFile);
+    }
+    // 重复文件
+    if hash_exists(conn, hash_value).await? {
+        println!("-->> {:<12} -- Duplicate File", "STORE_FILE");
+        return Err(Error::DuplicateFile);
+    }
+    // 识别格式、编码并提取文本，解析PDF等格式很耗CPU，不在异步线程中进行
+    let extracted = {
+        let filename = filename.to_string();
+        let data = data.to_vec();
+        tokio::task::spawn_blocking(move || extract_text(&filename, &data))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|res| res)
+    }
+    .map_err(|e| {
+        println!("-->> {:<12} -- UnSupportFileType {e:?}", "STORE_FILE");
+        Error::UnsportFileType
+    })?;
+    let content = TxtContent {
+        hash: hash_value.to_string(),
+        format: extracted.format.as_str().to_string(),
+        encoding: extracted.encoding.map(|e| e.name().to_string()),
+        size_bytes: data.len() as u64,
+        char_count: extracted.text.chars().count() as u64,
+        simhash: simhash(&extracted.text),
+    };
+    Ok((content, extracted.text))
+}
+
+/// 原文件写入本地，提取出的文本与原文件不同时另存一份UTF-8文本
+pub(crate) async fn write_checked_file(hash_value: &str, data: &[u8], text: &str) -> Result<()> {
+    write_file(hash_value, data)
+        .await
+        .map_err(|_| Error::InternalError)?;
+    if text.as_bytes() != data {
+        write_text_file(hash_value, text)
+            .await
+            .map_err(|_| Error::InternalError)?;
+    }
+    Ok(())
+}
+
+/// 检查文件并写入本地
+pub(crate) async fn store_file(
+    conn: &DatabaseConnection,
+    filename: &str,
+    data: &[u8],
+    hash_value: &str,
+) -> Result<(TxtContent, String)> {
+    let (content, text) = check_file(conn, filename, data, hash_value).await?;
+    write_checked_file(hash_value, data, &text).await?;
+    Ok((content, text))
+}
+
+/// 文档的标签和所在文件夹的路径
+pub(crate) async fn doc_meta(conn: &DatabaseConnection, doc: &txt::Model) -> Result<DocMeta> {
+    let folder_path = match doc.folder_id {
+        Some(folder_id) => get_folder_path(conn, folder_id).await?,
+        None => Vec::new(),
+    };
+    Ok(DocMeta {
+        tags: get_tags_by_txt_id(conn, doc.id).await?,
+        folder_path,
+    })
+}
+
+/// 按数据库中的信息重建某个文档的索引
+pub(crate) async fn reindex_doc(state: &AppState, doc: &txt::Model) -> Result<()> {
+    let _ = state.search.delete_doc(doc.id).await;
+    let body = read_file(doc.hash.clone()).await?;
+    let meta = doc_meta(&state.conn, doc).await?;
+    state
+        .search
+        .add_doc_with_meta(doc, &meta, body)
+        .await
+        .map_err(|_| Error::InternalError)
+}
+
+/// 重建一批文档的索引，文档的所有者或所在文件夹改变后使用，
+/// 按id重新读取数据库中的信息，已不存在或在回收站中的跳过
+pub(crate) async fn reindex_docs(state: &AppState, docs: &[txt::Model]) -> Result<()> {
+    let ids: Vec<u64> = docs.iter().map(|doc| doc.id).collect();
+    for doc in get_txt_by_ids(&state.conn, &ids).await? {
+        reindex_doc(state, &doc).await?;
+    }
+    Ok(())
+}
+
+/// 上传结果，near_duplicates为用户可以查看的近似重复的已有文档
+#[derive(Clone, Debug, Serialize)]
+pub struct UploadResult {
+    #[serde(flatten)]
+    doc: txt::Model,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    near_duplicates: Vec<txt::Model>,
+}
 
-/// 保存文件
+/// 保存文件，放入folder中时level为文件夹的默认level
 async fn save_file(
     state: AppState,
     claims: Claims,
+    folder: Option<folder::Model>,
     filename: String,
     data: Vec<u8>,
     hash_value: String,
-) -> Result<txt::Model> {
+) -> Result<UploadResult> {
     println!("-->> {:<12} -- Saving {filename:?}", "SAVE_FILE");
-    // 空文件
-    if data.len() == 0 {
-        println!("-->> {:<12} -- EMPTY_FILE", "SAVE_FILE");
-        return Err(Error::EmptyFile);
-    }
-    // 重复文件
-    match get_txt_by_hash(&state.conn, &hash_value).await? {
-        Some(_) => {
-            println!("-->> {:<12} -- Duplicate File", "SAVE_FILE");
-            return Err(Error::DuplicateFile);
+    let (content, text) = check_file(&state.conn, &filename, &data, &hash_value).await?;
+    // 近似重复的文档
+    let near_duplicates = match content.simhash {
+        Some(simhash) => find_near_duplicates(&state.conn, simhash)
+            .await
+            .map_err(|_| Error::InternalError)?,
+        None => Vec::new(),
+    };
+    // 只考虑用户可以查看的，与查看文档时的权限一致，不能借此探测看不到的文档
+    let mut visible_duplicates = Vec::with_capacity(near_duplicates.len());
+    for doc in near_duplicates {
+        match visible_doc(&state.conn, &claims, Some(doc)).await {
+            Ok(doc) => visible_duplicates.push(doc),
+            Err(Error::NoSuchFile) => {}
+            Err(e) => return Err(e),
         }
-        None => (),
     }
-    // 转化为UTF-8并验证
-    let txt = String::from_utf8(data).map_err(|_| {
-        println!("-->> {:<12} -- UnSupportFileType", "SAVE_FILE");
-        Error::UnsportFileType
-    })?;
+    if !visible_duplicates.is_empty()
+        && find_near_duplicate_policy_from_env() == NearDuplicatePolicy::Reject
+    {
+        println!("-->> {:<12} -- {filename:?} Near Duplicate", "SAVE_FILE");
+        return Err(Error::NearDuplicateFile);
+    }
+    write_checked_file(&hash_value, &data, &text).await?;
     // 文件信息写入数据库
-    let id: u64 = add_txt_info(
-        &state.conn,
-        &filename,
-        &hash_value,
-        &claims.id,
-        &claims.level,
-    )
-    .await?;
-    //  文件写入本地
-    let _ = write_file(&hash_value, txt.as_bytes())
+    let level = match &folder {
+        Some(folder) => min(folder.level, claims.level),
+        None => claims.level,
+    };
+    let id: u64 = add_txt_info(&state.conn, &filename, &claims.id, &level, &content).await?;
+    let mut new_txt_info: txt::Model = get_txt_by_id(&state.conn, id)
+        .await?
+        .ok_or(Error::InternalError)?;
+    if let Some(folder) = &folder {
+        new_txt_info = move_txt_to_folder(&state.conn, new_txt_info, Some(folder.id)).await?;
+    }
+    // 形成索引
+    let meta = doc_meta(&state.conn, &new_txt_info).await?;
+    state
+        .search
+        .add_doc_with_meta(&new_txt_info, &meta, text)
         .await
         .map_err(|_| Error::InternalError)?;
-    // 形成索引
-    add_doc_to_index(id, filename.clone(), txt, claims.level).await;
 
     // 返回信息
-    let new_txt_info: txt::Model = get_txt_by_id(&state.conn, id)
-        .await?
-        .ok_or(Error::InternalError)?;
     println!("-->> {:<12} -- {filename:?} Saved", "SAVE_FILE");
-    Ok(new_txt_info)
+    Ok(UploadResult {
+        doc: new_txt_info,
+        near_duplicates: visible_duplicates,
+    })
+}
+
+/// 读取multipart中一个文件的内容，同时计算sha256
+pub(crate) async fn read_field(field: &mut Field<'_>) -> Result<(Vec<u8>, String)> {
+    let mut ctx = Context::new(&SHA256);
+    let mut data: Vec<u8> = Vec::with_capacity(1024);
+    while let Some(bytes) = field.chunk().await.map_err(|_| Error::UploadFail)? {
+        ctx.update(&bytes);
+        data.extend(bytes);
+    }
+    let hash_value: String = HEXUPPER.encode(ctx.finish().as_ref());
+    Ok((data, hash_value))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadArg {
+    // 上传到自己的文件夹中
+    folder: Option<u64>,
+}
+
+impl UploadArg {
+    /// 上传的目标文件夹，需要是调用者的
+    async fn folder(&self, state: &AppState, claims: &Claims) -> Result<Option<folder::Model>> {
+        let Some(folder_id) = self.folder else {
+            return Ok(None);
+        };
+        match get_folder_by_id(&state.conn, folder_id).await? {
+            Some(folder) if folder.user_id == claims.id => Ok(Some(folder)),
+            _ => Err(Error::NoSuchFolder),
+        }
+    }
 }
 
 /// 单文件上传，接受multipartform，成功返回文件信息，失败返回错误信息
 pub async fn upload_doc_api(
     State(state): State<AppState>,
     claims: Claims,
+    Query(upload_arg): Query<UploadArg>,
     mut multipart: Multipart,
-) -> Result<Json<txt::Model>> {
+) -> Result<Json<UploadResult>> {
+    let folder = upload_arg.folder(&state, &claims).await?;
     if let Some(mut field) = multipart
         .next_field()
         .await
         .map_err(|_| Error::UploadFail)?
     {
-        let mut ctx = Context::new(&SHA256);
         let filename = match field.name() {
             Some(n) if !n.is_empty() => n.to_string(),
             _ => match field.file_name() {
@@ -94,13 +262,8 @@ pub async fn upload_doc_api(
             },
         };
         println!("Receiving {}", filename);
-        let mut data: Vec<u8> = Vec::with_capacity(1024);
-        while let Some(bytes) = field.chunk().await.map_err(|_| Error::UploadFail)? {
-            ctx.update(&bytes);
-            data.extend(bytes);
-        }
-        let hash_value: String = HEXUPPER.encode(ctx.finish().as_ref());
-        let doc = save_file(state, claims, filename, data, hash_value).await?;
+        let (data, hash_value) = read_field(&mut field).await?;
+        let doc = save_file(state, claims, folder, filename, data, hash_value).await?;
         Ok(Json(doc))
     } else {
         Err(Error::EmptyFile)
@@ -111,9 +274,11 @@ pub async fn upload_doc_api(
 pub async fn upload_docs_api(
     State(state): State<AppState>,
     claims: Claims,
+    Query(upload_arg): Query<UploadArg>,
     mut multipart: Multipart,
-) -> Result<Json<Vec<txt::Model>>> {
-    let mut upload_success = Vec::<txt::Model>::with_capacity(16);
+) -> Result<Json<Vec<UploadResult>>> {
+    let folder = upload_arg.folder(&state, &claims).await?;
+    let mut upload_success = Vec::<UploadResult>::with_capacity(16);
     let mut join_handlers = Vec::with_capacity(16);
 
     while let Some(mut field) = multipart
@@ -121,7 +286,6 @@ pub async fn upload_docs_api(
         .await
         .map_err(|_| Error::UploadFail)?
     {
-        let mut ctx = Context::new(&SHA256);
         let filename = match field.name() {
             Some(n) if !n.is_empty() => n.to_string(),
             _ => match field.file_name() {
@@ -130,14 +294,16 @@ pub async fn upload_docs_api(
             },
         };
         println!("Receiving {}", filename);
-        let mut data: Vec<u8> = Vec::with_capacity(1024);
-        while let Some(bytes) = field.chunk().await.map_err(|_| Error::UploadFail)? {
-            ctx.update(&bytes);
-            data.extend(bytes);
-        }
-        let hash_value: String = HEXUPPER.encode(ctx.finish().as_ref());
+        let (data, hash_value) = read_field(&mut field).await?;
 
-        let f = save_file(state.clone(), claims.clone(), filename, data, hash_value);
+        let f = save_file(
+            state.clone(),
+            claims.clone(),
+            folder.clone(),
+            filename,
+            data,
+            hash_value,
+        );
         join_handlers.push(tokio::spawn(f));
     }
     for jh in join_handlers {
@@ -159,11 +325,24 @@ pub async fn upload_docs_api(
 pub async fn docs_info_api(
     State(state): State<AppState>,
     claims: Claims,
+    docs_arg: Query<DocsArg>,
 ) -> Result<Json<Vec<txt::Model>>> {
-    let res = get_all_txt_lte_level(&state.conn, claims.level).await?;
+    let sort = Sort {
+        by: docs_arg.sort.as_deref().unwrap_or_default().into(),
+        order: docs_arg.order.as_deref().unwrap_or_default().into(),
+    };
+    let granted = get_granted_txt_ids(&state.conn, claims.id, false).await?;
+    let res = get_all_txt_lte_level(&state.conn, claims.level, &granted, sort).await?;
     Ok(Json(res))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DocsArg {
+    // 排序，默认按id从大到小，order与/query一样默认为desc
+    sort: Option<String>,
+    order: Option<String>,
+}
+
 /// 查看可以查看的文档
 pub async fn doc_info_api(
     State(state): State<AppState>,
@@ -171,10 +350,7 @@ pub async fn doc_info_api(
     Path(doc_id): Path<u64>,
 ) -> Result<Json<txt::Model>> {
     let doc = get_txt_by_id(&state.conn, doc_id).await?;
-    match doc {
-        Some(doc) if doc.level <= claims.level => Ok(Json(doc)),
-        _ => Err(Error::NoSuchFile),
-    }
+    Ok(Json(visible_doc(&state.conn, &claims, doc).await?))
 }
 /// 根据hash查看文档信息
 pub async fn doc_info_hash_api(
@@ -184,74 +360,284 @@ pub async fn doc_info_hash_api(
 ) -> Result<Json<txt::Model>> {
     let hash = hash.to_ascii_uppercase();
     let doc = get_txt_by_hash(&state.conn, &hash).await?;
-    match doc {
-        Some(doc) if doc.level <= claims.level => Ok(Json(doc)),
-        _ => Err(Error::NoSuchFile),
-    }
+    Ok(Json(visible_doc(&state.conn, &claims, doc).await?))
 }
 
-/// 删除文档
+/// 删除文档，放入回收站
 pub async fn delete_doc_api(
     State(state): State<AppState>,
     claims: Claims,
     Path(doc_id): Path<u64>,
 ) -> Result<Json<Msg>> {
     let doc = get_txt_by_id(&state.conn, doc_id).await?;
-    let doc = match doc {
-        Some(doc) if claims.id == doc.user_id => doc,
-        _ => return Err(Error::NoSuchFile),
-    };
+    let doc = writable_doc(&state.conn, &claims, doc).await?;
 
+    // 放入回收站
+    let doc = trash_txt(&state.conn, doc).await?;
     // 从索引中删除
-    let _ = delete_from_index(doc.id).await;
-    // 从文件系统中删除
-    let _ = delete_file(&doc.hash).await;
-    // 从数据库中删除
-    let _ = delete_txt_info(&state.conn, doc).await;
+    state
+        .search
+        .delete_doc(doc.id)
+        .await
+        .map_err(|_| Error::InternalError)?;
 
     Ok(Json(Msg::from("Ok")))
 }
 
+// 每页默认和最大条数
+const DEFAULT_QUERY_LIMIT: usize = 20;
+const MAX_QUERY_LIMIT: usize = 100;
+
 #[derive(Deserialize, Clone)]
 pub struct QueryArg {
     query_string: String,
     field: Option<String>,
+    // 分页
+    offset: Option<usize>,
     limit: Option<usize>,
+    // 高亮
+    highlight: Option<bool>,
+    fragment_size: Option<usize>,
+    pre_tag: Option<String>,
+    post_tag: Option<String>,
+    // 排序，默认按相关度从高到低
+    sort: Option<String>,
+    order: Option<String>,
+    // 过滤，owner、format和tag为逗号分隔的列表，时间为RFC 3339格式
+    owner: Option<String>,
+    min_level: Option<u8>,
+    max_level: Option<u8>,
+    created_from: Option<DateTimeUtc>,
+    created_to: Option<DateTimeUtc>,
+    format: Option<String>,
+    tag: Option<String>,
+    // 只搜索这个文件夹及其子文件夹
+    folder: Option<u64>,
+    // 模糊匹配，distance为编辑距离，默认为1
+    fuzzy: Option<bool>,
+    distance: Option<u8>,
+    // 同义词扩展，默认开启
+    synonym: Option<bool>,
 }
+
+impl QueryArg {
+    /// 解析过滤条件
+    fn search_filter(&self) -> Result<SearchFilter> {
+        let split = |s: &Option<String>| -> Vec<String> {
+            s.as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        let owners = split(&self.owner)
+            .iter()
+            .map(|id| id.parse::<u64>().map_err(|_| Error::ErrorSearchQuery))
+            .collect::<Result<Vec<u64>>>()?;
+        // 与保存时一样处理，非ASCII的大写字母也转为小写
+        let tags = split(&self.tag)
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<Vec<String>>>()?;
+        Ok(SearchFilter {
+            owners,
+            min_level: self.min_level,
+            max_level: self.max_level,
+            created_from: self.created_from,
+            created_to: self.created_to,
+            formats: split(&self.format),
+            tags,
+            folder_path: None,
+        })
+    }
+
+    /// 需要高亮时返回片段参数
+    fn snippet_options(&self) -> Option<SnippetOptions> {
+        if !self.highlight.unwrap_or(false) {
+            return None;
+        }
+        let default = SnippetOptions::default();
+        Some(SnippetOptions {
+            fragment_size: self.fragment_size.unwrap_or(default.fragment_size),
+            pre_tag: self.pre_tag.clone().unwrap_or(default.pre_tag),
+            post_tag: self.post_tag.clone().unwrap_or(default.post_tag),
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct QueryResult {
     doc: txt::Model,
     score: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlight: Option<Highlight>,
 }
 
 impl QueryResult {
-    pub fn new(doc: txt::Model, score: f32) -> QueryResult {
-        QueryResult { doc, score }
+    pub fn new(doc: txt::Model, score: f32, highlight: Option<Highlight>) -> QueryResult {
+        QueryResult {
+            doc,
+            score,
+            highlight,
+        }
     }
 }
 
+/// 查询结果分页，total为命中总数
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct QueryPage {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    results: Vec<QueryResult>,
+    // 索引中存在但数据库中已不存在的文档id，已从索引中删除
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pruned: Vec<u64>,
+    // 命中文档的各标签计数
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<TagCount>,
+}
+
 /// 查询api
+///
+/// 命中的文档用一次IN查询取回，结果保持得分顺序。
+/// 数据库中已不存在的命中不会被静默丢弃：它们从索引中删除，
+/// 从total中扣除，并在pruned中返回。
 pub async fn query_api(
     State(state): State<AppState>,
     claims: Claims,
     query_arg: Query<QueryArg>,
-) -> Result<Json<Vec<QueryResult>>> {
+) -> Result<Json<QueryPage>> {
     let query_string = decode(&query_arg.query_string).map_err(|_| Error::ErrorSearchQuery)?;
-    let limit = query_arg.limit.to_owned();
+    let offset = query_arg.offset.unwrap_or(0);
+    let limit = min(query_arg.limit.unwrap_or(DEFAULT_QUERY_LIMIT), MAX_QUERY_LIMIT);
     let field = SearchField::from(query_arg.field.to_owned().unwrap_or("All".to_string()));
+    let mut filter = query_arg.search_filter()?;
+    if let Some(folder_id) = query_arg.folder {
+        // 与查看文件夹的权限一致，不能借此探测别人的文件夹
+        let folder = viewable_folder(&state, &claims, folder_id).await?;
+        filter.folder_path = Some(get_folder_path(&state.conn, folder.id).await?);
+    }
+    let options = SearchOptions {
+        field,
+        level: claims.level,
+        offset,
+        limit,
+        sort: Sort {
+            by: query_arg.sort.as_deref().unwrap_or_default().into(),
+            order: query_arg.order.as_deref().unwrap_or_default().into(),
+        },
+        filter,
+        fuzzy: query_arg
+            .fuzzy
+            .unwrap_or(false)
+            .then(|| query_arg.distance.unwrap_or(1)),
+        synonyms: query_arg.synonym.unwrap_or(true),
+        snippet: query_arg.snippet_options(),
+        granted: get_granted_txt_ids(&state.conn, claims.id, false).await?,
+    };
 
-    let res_id = search_from_rev_index(field, &query_string, claims.level, limit.unwrap_or(0))
+    let page = state
+        .search
+        .search(&query_string, &options)
         .map_err(|_| Error::ErrorSearchQuery)?;
 
-    let mut res = Vec::new();
-    for (id, score) in res_id {
-        // println!("-->>{:<12} -- {id}", "QUERY_API");
-        match get_txt_by_id(&state.conn, id).await? {
-            Some(doc) => res.push(QueryResult::new(doc, score)),
-            None => (),
+    let ids: Vec<u64> = page.hits.iter().map(|hit| hit.id).collect();
+    let mut docs: HashMap<u64, txt::Model> = get_txt_by_ids(&state.conn, &ids)
+        .await?
+        .into_iter()
+        .map(|doc| (doc.id, doc))
+        .collect();
+
+    let mut results = Vec::with_capacity(page.hits.len());
+    let mut pruned = Vec::new();
+    for hit in page.hits {
+        match docs.remove(&hit.id) {
+            Some(doc) => results.push(QueryResult::new(doc, hit.score, hit.highlight)),
+            None => {
+                println!("-->> {:<12} -- doc {} not in database, prune it", "QUERY_API", hit.id);
+                state
+                    .search
+                    .delete_doc(hit.id)
+                    .await
+                    .map_err(|_| Error::InternalError)?;
+                pruned.push(hit.id);
+            }
         }
     }
-    Ok(Json(res))
+    Ok(Json(QueryPage {
+        total: page.total - pruned.len(),
+        offset,
+        limit,
+        results,
+        pruned,
+        tags: page.tags,
+    }))
+}
+
+const DEFAULT_SUGGEST_LIMIT: usize = 5;
+const MAX_SUGGEST_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestArg {
+    prefix: String,
+    limit: Option<usize>,
+}
+
+/// 输入时补全标题和词
+pub async fn suggest_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    suggest_arg: Query<SuggestArg>,
+) -> Result<Json<Suggestions>> {
+    let limit = min(suggest_arg.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT), MAX_SUGGEST_LIMIT);
+    let granted = get_granted_txt_ids(&state.conn, claims.id, false).await?;
+    let suggestions = state
+        .search
+        .suggest(&suggest_arg.prefix, claims.level, &granted, limit)
+        .map_err(|_| Error::ErrorSearchQuery)?;
+    Ok(Json(suggestions))
+}
+
+const DEFAULT_SIMILAR_LIMIT: usize = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct SimilarArg {
+    limit: Option<usize>,
+}
+
+/// 与某个文档相似的文档，只返回可以查看的
+pub async fn similar_docs_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+    similar_arg: Query<SimilarArg>,
+) -> Result<Json<Vec<QueryResult>>> {
+    let doc = get_txt_by_id(&state.conn, doc_id).await?;
+    visible_doc(&state.conn, &claims, doc).await?;
+    let limit = min(similar_arg.limit.unwrap_or(DEFAULT_SIMILAR_LIMIT), MAX_QUERY_LIMIT);
+    let granted = get_granted_txt_ids(&state.conn, claims.id, false).await?;
+    let hits = state
+        .search
+        .similar(doc_id, claims.level, &granted, limit)
+        .map_err(|_| Error::InternalError)?
+        .ok_or(Error::NoSuchFile)?;
+
+    let ids: Vec<u64> = hits.iter().map(|hit| hit.id).collect();
+    let mut docs: HashMap<u64, txt::Model> = get_txt_by_ids(&state.conn, &ids)
+        .await?
+        .into_iter()
+        .map(|doc| (doc.id, doc))
+        .collect();
+    let results = hits
+        .into_iter()
+        .filter_map(|hit| {
+            let doc = docs.remove(&hit.id)?;
+            Some(QueryResult::new(doc, hit.score, None))
+        })
+        .collect();
+    Ok(Json(results))
 }
 
 #[derive(Clone, Deserialize)]
@@ -267,11 +653,12 @@ pub async fn update_doc_api(
     Json(payload): Json<UpdateDocInfo>,
 ) -> Result<Json<txt::Model>> {
     let doc = get_txt_by_id(&state.conn, doc_id).await?;
-    let doc = match doc {
-        // 验证权限
-        Some(doc) if doc.user_id == claims.id => doc,
-        _ => return Err(Error::NoSuchFile),
-    };
+    // 验证权限
+    let doc = writable_doc(&state.conn, &claims, doc).await?;
+    // 只有所有者可以修改level
+    if payload.level.is_some() && doc.user_id != claims.id {
+        return Err(Error::InvalidLevel);
+    }
     let title = {
         // 字符串非空，否则为原title
         if let Some(tit) = payload.title {
@@ -310,49 +697,85 @@ pub async fn update_doc_api(
     // 修改数据库
     let doc = update_doc_info(&state.conn, doc, title, level).await?;
     // 修改索引
-    let _ = delete_from_index(doc.id).await;
-    let body = read_file(doc.hash.clone()).await?;
-    add_doc_to_index(doc.id, doc.title.clone(), body, doc.level).await;
+    reindex_doc(&state, &doc).await?;
     Ok(Json(doc))
 }
 
 /// 重建索引，需要admin
 pub async fn rebuild_index_api(State(state): State<AppState>, claims: Claims) -> Result<Json<Msg>> {
     if claims.is_admin == 1 {
-        rebuild_search_index(state.conn).await;
+        state
+            .search
+            .rebuild(&state.conn)
+            .await
+            .map_err(|_| Error::InternalError)?;
         Ok(Json(Msg::from("Ok")))
     } else {
         Err(Error::InvalidToken)
     }
 }
 
+#[derive(Deserialize, Clone)]
+pub struct DownloadArg {
+    // 为true时下载转为UTF-8的文本，否则下载原文件
+    pub(crate) utf8: Option<bool>,
+}
+
 /// 下载文件
 pub async fn download_api(
     State(state): State<AppState>,
     claims: Claims,
     Path(hash): Path<String>,
-) -> Result<(HeaderMap, String)> {
+    Query(download_arg): Query<DownloadArg>,
+) -> Result<(HeaderMap, Vec<u8>)> {
     let hash = hash.to_ascii_uppercase();
     // 获取文件信息
-    let doc = match get_txt_by_hash(&state.conn, &hash).await? {
-        // 鉴权
-        Some(doc) if doc.level <= claims.level => Ok(doc),
-        _ => Err(Error::NoSuchFile),
-    }?;
+    // 鉴权
+    let doc = get_txt_by_hash(&state.conn, &hash).await?;
+    let doc = visible_doc(&state.conn, &claims, doc).await?;
+
+    file_response(
+        &doc.title,
+        &doc.format,
+        doc.encoding.as_deref(),
+        &hash,
+        download_arg.utf8.unwrap_or(false),
+    )
+    .await
+}
+
+/// 构造下载文件的响应，utf8为true时返回转为UTF-8的文本
+pub(crate) async fn file_response(
+    title: &str,
+    format: &str,
+    encoding: Option<&str>,
+    hash: &str,
+    utf8: bool,
+) -> Result<(HeaderMap, Vec<u8>)> {
+    let format = DocFormat::from(format);
+    let content_type = if utf8 {
+        "text/plain; charset=utf-8".to_string()
+    } else if format.is_text() {
+        let charset = encoding.unwrap_or("utf-8");
+        format!("{}; charset={charset}", format.mime())
+    } else {
+        format.mime().to_string()
+    };
 
     // 设置头
     let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        "text/plain; charset=utf-8".parse().unwrap(),
-    );
+    headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
     headers.insert(
         header::CONTENT_DISPOSITION,
-        format!("attachment; filename=\"{}\"", encode(&doc.title))
+        format!("attachment; filename=\"{}\"", encode(title))
             .parse()
             .unwrap(),
     );
-    let body = read_file(hash).await?;
+    let body = if utf8 {
+        read_file(hash.to_string()).await?.into_bytes()
+    } else {
+        read_raw_file(hash).await?
+    };
 
     Ok((headers, body))
 }
@@ -4,7 +4,8 @@ use serde::Deserialize;
 
 use crate::database::mutation::{add_user, delete_user, update_user_info};
 use crate::database::query::{
-    get_all_users, get_txt_by_user_id, get_txt_maxlevel_by_userid, get_user_by_id, get_user_by_name,
+    get_all_users, get_folders_by_user_id, get_txt_by_user_id, get_txt_maxlevel_by_userid,
+    get_user_by_id, get_user_by_name,
 };
 use crate::entities::user::Model;
 use crate::Msg;
@@ -12,6 +13,7 @@ use crate::{entities::user, AppState};
 
 use super::error::*;
 use super::login::Claims;
+use super::txt::reindex_docs;
 
 const EMPTY_PASSWORD: &str = "EC3395932920B3DA7BE44CFE7673CA15EA24DE40214905CEE00EE274F8C1CE6F";
 
@@ -24,7 +26,7 @@ impl Model {
 
 /// 验证是否为admin
 #[inline]
-fn validate_admin(claims: &Claims) -> Result<()> {
+pub(crate) fn validate_admin(claims: &Claims) -> Result<()> {
     if claims.is_admin == 0 {
         return Err(Error::InvalidToken);
     }
@@ -200,8 +202,10 @@ pub async fn delete_user_api(
     let user = get_user_by_id(&state.conn, id)
         .await?
         .ok_or(Error::NoSuchUser)?;
-    // 若无文档
-    if get_txt_by_user_id(&state.conn, id).await?.is_empty() {
+    // 若无文档和文件夹
+    if get_txt_by_user_id(&state.conn, id).await?.is_empty()
+        && get_folders_by_user_id(&state.conn, id).await?.is_empty()
+    {
         delete_user(&state.conn, user, None).await?;
         Ok(okmsg)
     // 若有文档
@@ -215,7 +219,10 @@ pub async fn delete_user_api(
                 Err(Error::InvalidMoveUser)
             // moveuser.level必须大于等于user.level，且moveuser和user不能是同一个
             } else {
+                let docs = get_txt_by_user_id(&state.conn, user.id).await?;
                 delete_user(&state.conn, user, Some(moveuser)).await?;
+                // 所有者是索引的排序和过滤条件，需要重新索引转移的文档
+                reindex_docs(&state, &docs).await?;
                 Ok(okmsg)
             }
         // 未提供moveuser
@@ -0,0 +1,85 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use super::error::*;
+use super::login::Claims;
+use super::user::validate_admin;
+use crate::database::mutation::{delete_user_word, save_user_word};
+use crate::database::query::get_all_user_words;
+use crate::database::user_dict::{load_user_dict, USER_WORD_MAX_LEN};
+use crate::entities::user_dict;
+use crate::{AppState, Msg};
+
+// 与数据库中tag列的长度一致
+const TAG_MAX_LEN: usize = 16;
+
+#[derive(Debug, Deserialize)]
+pub struct UserWordArg {
+    word: String,
+    // 词频，为空时由jieba估计
+    freq: Option<u32>,
+    // 词性，如n、nz
+    tag: Option<String>,
+}
+
+/// 用户词典修改后重新加载分词器，并在后台重建索引
+async fn reload_user_dict(state: &AppState) -> Result<()> {
+    load_user_dict(&state.conn, &state.search)
+        .await
+        .map_err(|_| Error::InternalError)?;
+    state.search.schedule_rebuild(state.conn.clone());
+    Ok(())
+}
+
+/// 查看用户词典，需要admin
+pub async fn user_dict_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<user_dict::Model>>> {
+    validate_admin(&claims)?;
+    Ok(Json(get_all_user_words(&state.conn).await?))
+}
+
+/// 添加或修改用户词典中的词，需要admin
+pub async fn save_user_word_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<UserWordArg>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    let word = payload.word.trim();
+    if word.is_empty()
+        || word.chars().any(char::is_whitespace)
+        || word.chars().count() > USER_WORD_MAX_LEN
+    {
+        return Err(Error::InvalidUserWord);
+    }
+    let tag = payload
+        .tag
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty());
+    if tag.as_ref().is_some_and(|tag| tag.len() > TAG_MAX_LEN) {
+        return Err(Error::InvalidUserWord);
+    }
+
+    save_user_word(&state.conn, word, payload.freq, tag).await?;
+    println!("-->> {:<12} -- save user word {word}", "USER_DICT");
+    reload_user_dict(&state).await?;
+    Ok(Json(Msg::from("Ok")))
+}
+
+/// 删除用户词典中的词，需要admin
+pub async fn delete_user_word_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(word): Path<String>,
+) -> Result<Json<Msg>> {
+    validate_admin(&claims)?;
+    if delete_user_word(&state.conn, &word).await? == 0 {
+        return Err(Error::NoSuchUserWord);
+    }
+    println!("-->> {:<12} -- delete user word {word}", "USER_DICT");
+    reload_user_dict(&state).await?;
+    Ok(Json(Msg::from("Ok")))
+}
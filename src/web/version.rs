@@ -0,0 +1,115 @@
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::HeaderMap;
+use axum::Json;
+
+use super::error::*;
+use super::grant::{visible_doc, writable_doc};
+use super::login::Claims;
+use super::txt::{file_response, read_field, reindex_doc, store_file, DownloadArg};
+use crate::database::mutation::{
+    add_txt_version, delete_txt_version, update_doc_content, TxtContent,
+};
+use crate::database::query::{get_txt_by_id, get_version_by_id, get_versions_by_txt_id};
+use crate::entities::{txt, txt_version};
+use crate::AppState;
+
+/// 找到用户可以修改的文档：用户拥有的，或者有可修改的授权
+async fn get_writable_doc(state: &AppState, claims: &Claims, doc_id: u64) -> Result<txt::Model> {
+    let doc = get_txt_by_id(&state.conn, doc_id).await?;
+    writable_doc(&state.conn, claims, doc).await
+}
+
+/// 找到用户可见文档的某个历史版本
+async fn get_visible_version(
+    state: &AppState,
+    claims: &Claims,
+    doc_id: u64,
+    version_id: u64,
+) -> Result<(txt::Model, txt_version::Model)> {
+    let doc = get_txt_by_id(&state.conn, doc_id).await?;
+    let doc = visible_doc(&state.conn, claims, doc).await?;
+    match get_version_by_id(&state.conn, version_id).await? {
+        Some(version) if version.txt_id == doc.id => Ok((doc, version)),
+        _ => Err(Error::NoSuchVersion),
+    }
+}
+
+/// 上传文档的新版本，旧内容存为历史版本，接受multipartform
+pub async fn upload_version_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+    mut multipart: Multipart,
+) -> Result<Json<txt::Model>> {
+    let doc = get_writable_doc(&state, &claims, doc_id).await?;
+    let mut field = multipart
+        .next_field()
+        .await
+        .map_err(|_| Error::UploadFail)?
+        .ok_or(Error::EmptyFile)?;
+    // 格式按新文件的文件名识别，没有则沿用标题
+    let filename = match field.file_name() {
+        Some(n) if !n.is_empty() => n.to_string(),
+        _ => doc.title.clone(),
+    };
+    let (data, hash_value) = read_field(&mut field).await?;
+    let (content, _) = store_file(&state.conn, &filename, &data, &hash_value).await?;
+
+    // 旧内容存为历史版本
+    add_txt_version(&state.conn, &doc).await?;
+    let doc = update_doc_content(&state.conn, doc, &content).await?;
+    // 索引始终为当前版本
+    reindex_doc(&state, &doc).await?;
+    println!("-->> {:<12} -- doc {} new version {hash_value}", "UPLOAD_VER", doc.id);
+    Ok(Json(doc))
+}
+
+/// 查看文档的历史版本
+pub async fn versions_info_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(doc_id): Path<u64>,
+) -> Result<Json<Vec<txt_version::Model>>> {
+    let doc = get_txt_by_id(&state.conn, doc_id).await?;
+    let doc = visible_doc(&state.conn, &claims, doc).await?;
+    Ok(Json(get_versions_by_txt_id(&state.conn, doc.id).await?))
+}
+
+/// 下载历史版本
+pub async fn download_version_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((doc_id, version_id)): Path<(u64, u64)>,
+    Query(download_arg): Query<DownloadArg>,
+) -> Result<(HeaderMap, Vec<u8>)> {
+    let (doc, version) = get_visible_version(&state, &claims, doc_id, version_id).await?;
+    file_response(
+        &doc.title,
+        &version.format,
+        version.encoding.as_deref(),
+        &version.hash,
+        download_arg.utf8.unwrap_or(false),
+    )
+    .await
+}
+
+/// 恢复历史版本，当前内容存为新的历史版本
+pub async fn restore_version_api(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((doc_id, version_id)): Path<(u64, u64)>,
+) -> Result<Json<txt::Model>> {
+    let doc = get_writable_doc(&state, &claims, doc_id).await?;
+    let version = match get_version_by_id(&state.conn, version_id).await? {
+        Some(version) if version.txt_id == doc.id => version,
+        _ => return Err(Error::NoSuchVersion),
+    };
+
+    let content = TxtContent::from(version.clone());
+    delete_txt_version(&state.conn, version).await?;
+    add_txt_version(&state.conn, &doc).await?;
+    let doc = update_doc_content(&state.conn, doc, &content).await?;
+    reindex_doc(&state, &doc).await?;
+    println!("-->> {:<12} -- doc {} restore {}", "RESTORE_VER", doc.id, content.hash);
+    Ok(Json(doc))
+}
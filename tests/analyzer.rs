@@ -0,0 +1,91 @@
+use ks_backend::database::analyzer::{split_pinyin, to_pinyin, AnalyzerOptions, SharedJieba};
+use ks_backend::entities::user_dict;
+use tantivy::tokenizer::{Language, TokenStream, Tokenizer};
+
+#[test]
+fn split_pinyin_syllables() {
+    assert_eq!(split_pinyin("luxien"), vec!["lu", "xi", "en"]);
+    assert_eq!(split_pinyin("lxe"), vec!["l", "x", "e"]);
+    assert_eq!(split_pinyin("zhuangxiu"), vec!["zhuang", "xiu"]);
+    assert_eq!(split_pinyin("tantivy"), vec!["tan", "ti", "v", "y"]);
+    assert!(split_pinyin("").is_empty());
+}
+
+#[test]
+fn hanzi_to_pinyin() {
+    let pinyin = to_pinyin("路西恩");
+    assert_eq!(
+        pinyin,
+        vec![
+            ("lu".to_string(), Some("l".to_string())),
+            ("xi".to_string(), Some("x".to_string())),
+            ("en".to_string(), Some("e".to_string())),
+        ]
+    );
+    assert_eq!(to_pinyin("绿")[0].0, "lv");
+    assert_eq!(to_pinyin("a")[0], ("a".to_string(), None));
+}
+
+#[test]
+fn analyzer_tokens() {
+    let options = AnalyzerOptions::default();
+    assert_eq!(options.tokenizer(), "jieba_t2s_english");
+    let mut analyzer = options.build_analyzer(SharedJieba::default(), Vec::new());
+    let mut stream = analyzer.token_stream("紅色的Running Café");
+    let mut tokens = Vec::new();
+    while stream.advance() {
+        tokens.push(stream.token().text.clone());
+    }
+    assert!(tokens.contains(&"红色".to_string()));
+    assert!(tokens.contains(&"run".to_string()));
+    assert!(tokens.contains(&"cafe".to_string()));
+
+    let options = AnalyzerOptions {
+        fold_traditional: false,
+        stem_language: Some(Language::French),
+    };
+    assert_eq!(options.tokenizer(), "jieba_french");
+}
+
+#[test]
+fn user_dict_tokens() {
+    let jieba = SharedJieba::default();
+    let tokens = |jieba: &SharedJieba| {
+        let mut jieba = jieba.clone();
+        let mut stream = jieba.token_stream("星火智聊上线");
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        tokens
+    };
+    assert!(!tokens(&jieba).contains(&"星火智聊".to_string()));
+
+    let word = user_dict::Model {
+        id: 1,
+        word: "星火智聊".to_string(),
+        freq: None,
+        tag: None,
+    };
+    jieba.set_user_dict(&[word]);
+    assert!(tokens(&jieba).contains(&"星火智聊".to_string()));
+}
+
+#[test]
+fn token_positions() {
+    let options = AnalyzerOptions::default();
+    let positions = |text: &str| {
+        let mut analyzer = options.build_analyzer(SharedJieba::default(), Vec::new());
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push((stream.token().text.clone(), stream.token().position));
+        }
+        tokens
+    };
+    // 位置按词数递增，与空白和标点无关
+    let expected = vec![("hello".to_string(), 0), ("world".to_string(), 1)];
+    assert_eq!(positions("hello world"), expected);
+    assert_eq!(positions("hello,   world"), expected);
+    assert_eq!(positions("你好，世界"), positions("你好世界"));
+}
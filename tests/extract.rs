@@ -0,0 +1,87 @@
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use ks_backend::database::extract::{decode_text, extract_text, DocFormat};
+
+#[test]
+fn detect_format() {
+    assert_eq!(DocFormat::detect("a.txt", "你好".as_bytes()), DocFormat::Plain);
+    assert_eq!(DocFormat::detect("a.MD", b"# title"), DocFormat::Markdown);
+    assert_eq!(DocFormat::detect("a", b"<!DOCTYPE html><p>x</p>"), DocFormat::Html);
+    assert_eq!(DocFormat::detect("a.bin", b"%PDF-1.7"), DocFormat::Pdf);
+    assert_eq!(DocFormat::detect("a.docx", b"PK\x03\x04"), DocFormat::Docx);
+    assert_eq!(DocFormat::from(DocFormat::Docx.as_str()), DocFormat::Docx);
+}
+
+#[test]
+fn extract_markdown_and_html() -> Result<()> {
+    let res = extract_text("a.md", "# 标题\n\n红色的**火焰**".as_bytes())?;
+    assert_eq!(res.format, DocFormat::Markdown);
+    assert_eq!(res.text, "标题\n红色的火焰\n");
+
+    let res = extract_text("a.html", "<html><body><p>红色的<b>火焰</b></p></body></html>".as_bytes())?;
+    assert_eq!(res.format, DocFormat::Html);
+    assert_eq!(res.text.trim(), "红色的火焰");
+    Ok(())
+}
+
+#[test]
+fn extract_docx() -> Result<()> {
+    let mut buf = Cursor::new(Vec::new());
+    let mut zip = zip::ZipWriter::new(&mut buf);
+    zip.start_file("word/document.xml", zip::write::SimpleFileOptions::default())?;
+    zip.write_all(
+        br#"<w:document><w:body><w:p><w:r><w:t>red &amp; </w:t><w:t>fire</w:t></w:r></w:p><w:p><w:r><w:t>burn</w:t></w:r></w:p></w:body></w:document>"#,
+    )?;
+    zip.finish()?;
+
+    let res = extract_text("a.docx", buf.get_ref())?;
+    assert_eq!(res.format, DocFormat::Docx);
+    assert_eq!(res.encoding, None);
+    assert_eq!(res.text, "red & fire\nburn\n");
+    Ok(())
+}
+
+#[test]
+fn decode_legacy_encodings() -> Result<()> {
+    let text = "红色的火焰在燃烧，照亮了整条街道。路西恩站在窗前，看着远处的城市。";
+
+    let (gbk, _, _) = encoding_rs::GBK.encode(text);
+    let res = extract_text("a.txt", &gbk)?;
+    assert_eq!(res.text, text);
+    assert_eq!(res.encoding.unwrap().name(), "GBK");
+
+    let traditional = "紅色的火焰在燃燒，照亮了整條街道。路西恩站在窗前，看著遠處的城市。";
+    let (big5, _, _) = encoding_rs::BIG5.encode(traditional);
+    let (decoded, encoding) = decode_text(&big5)?;
+    assert_eq!(decoded, traditional);
+    assert_eq!(encoding.name(), "Big5");
+
+    let (decoded, encoding) = decode_text("\u{feff}你好".as_bytes())?;
+    assert_eq!(decoded, "你好");
+    assert_eq!(encoding.name(), "UTF-8");
+    Ok(())
+}
+
+#[test]
+fn reject_binary() {
+    // PNG、XLSX（zip）、EXE的文件头和一段任意字节
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend((0..=255u8).rev());
+    let xlsx = b"PK\x03\x04\x14\0\x06\0\x08\0\0\0!\0\xd4\x9f\x1c\x8a[Content_Types].xml";
+    let exe = b"MZ\x90\0\x03\0\0\0\x04\0\0\0\xff\xff\0\0\xb8\0\0\0\0\0\0\0@\0";
+    for data in [&png[..], &xlsx[..], &exe[..]] {
+        assert!(decode_text(data).is_err());
+        assert!(extract_text("a.bin", data).is_err());
+    }
+    // 没有控制字符但不是中文编码的文本
+    assert!(decode_text(b"caf\xe9 cr\xe8me br\xfbl\xe9e").is_err());
+}
+
+#[test]
+fn reject_malformed_pdf() {
+    // 只有文件头的PDF和截断的PDF，解析失败或panic都应当返回错误
+    for data in [&b"%PDF-1.7"[..], &b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R"[..]] {
+        assert!(extract_text("a.pdf", data).is_err());
+    }
+}
@@ -5,24 +5,57 @@ use core::time;
 use std::thread::sleep;
 
 use anyhow::Result;
-use ks_backend::database::{db::get_db, mutation::{add_txt_info, add_user, delete_file, delete_txt_info, delete_user, write_file}, query::{get_txt_by_id, get_user_by_id, read_file}, search::{commiting, init_index, rebuild_search_index, search_from_rev_index, SearchField}};
+use chrono::{Duration, Utc};
+use ks_backend::database::{db::get_db, metadata::backfill_metadata, trash::purge_txt, simhash::{find_near_duplicates, simhash}, mutation::{update_txt_metadata, add_group, add_group_member, add_grant, delete_group, NewGrant, add_stopwords, add_folder, delete_folder, move_txt_to_folder, add_txt_tags, remove_txt_tag, add_synonyms, delete_synonym, delete_stopword, save_user_word, delete_user_word, add_txt_info, add_txt_version, update_doc_info, add_user, restore_txt, trash_txt, delete_file, delete_txt_info, delete_user, write_file, TxtContent}, query::{get_folders_by_parent, get_txt_by_folder_id, get_granted_txt_ids, get_grants_by_txt_id, get_group_by_id, has_grant, get_all_stopwords, folder_is_empty, get_folder_by_id, get_folder_path, get_tags_by_txt_id, get_all_synonyms, get_all_user_words, get_trashed_txt_by_id, get_txt_by_id, get_user_by_id, get_versions_by_txt_id, read_file}, search::{SearchField, SearchService}};
+use ks_backend::entities::user;
+use ks_backend::web::error::Error;
+use ks_backend::web::grant::{owned_doc, visible_doc, writable_doc};
+use ks_backend::web::login::Claims;
+use ks_backend::AppState;
 use ring::digest::{Context, SHA256};
 use data_encoding::HEXUPPER;
+use sea_orm::DatabaseConnection;
 
 
-
-
-#[tokio::test]
-async fn query_test() -> Result<()> {
+async fn connect() -> DatabaseConnection {
     println!("Connect to DataBase...");
     let conn: sea_orm::prelude::DatabaseConnection = match get_db().await {
         Ok(db) => db,
         Err(err) => panic!("{}", err),
     };
     println!("DataBase Connected ...");
-    
+    conn
+}
+
+/// 添加一个用户和他的一个文档，各个测试使用不同的用户名和hash
+async fn add_test_doc(conn: &DatabaseConnection, username: &str, hash: &str) -> (user::Model, u64) {
+    let id = add_user(conn, username, "test", 0, false).await.expect("Add User Failure");
+    let user = get_user_by_id(conn, id).await.unwrap().unwrap();
+    let content = TxtContent {
+        hash: hash.to_string(),
+        format: "plain".to_string(),
+        encoding: Some("UTF-8".to_string()),
+        size_bytes: hash.len() as u64,
+        char_count: hash.chars().count() as u64,
+        simhash: simhash(hash),
+    };
+    let doc_id = add_txt_info(conn, hash, &id, &0, &content).await.unwrap();
+    (user, doc_id)
+}
+
+/// 删除测试用的文档和用户
+async fn remove_test_doc(conn: &DatabaseConnection, user: user::Model, doc_id: u64) {
+    let doc = get_txt_by_id(conn, doc_id).await.unwrap().unwrap();
+    delete_txt_info(conn, doc).await.unwrap();
+    delete_user(conn, user, None).await.expect("Delete User Failure");
+}
+
+#[tokio::test]
+async fn query_test() -> Result<()> {
+    let conn = connect().await;
+
     // 文件写入测试
-    
+
     let hash = "114514";
     let data = "1919810".as_bytes();
 
@@ -31,7 +64,7 @@ async fn query_test() -> Result<()> {
     } else {
         println!("Err");
     }
-    
+
     if delete_file(hash).await.is_ok() {
         println!("Ok");
     } else {
@@ -40,26 +73,259 @@ async fn query_test() -> Result<()> {
 
     // 用户增删测试
 
-    let id = add_user(&conn, "test", "test", 0, false).await.expect("Add User Failure");
+    // 添加一个用户和一个文档
+    let (user, doc_id) = add_test_doc(&conn, "test", "123").await;
     println!("Add user Ok");
-    // 添加一个用户
-    let user = get_user_by_id(&conn, id).await.unwrap().unwrap();
-    // 添加一个文档
-    let doc_id = add_txt_info(&conn, "123", "123", &id, &0).await.unwrap();
-    // 尝试删除，应该失败    
+    // 尝试删除，应该失败
     if delete_user(&conn, user.clone(), None).await.is_ok() {
         panic!("Test Has Doc");
     } else {
         println!("Ok")
     }
-    // 删除该文档
+    // 删除该文档，再次删除，应该成功
+    remove_test_doc(&conn, user, doc_id).await;
+
+    println!("Delete User Ok");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn version_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_version", "version").await;
+
+    let doc = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    add_txt_version(&conn, &doc).await.unwrap();
+    assert_eq!(get_versions_by_txt_id(&conn, doc_id).await.unwrap().len(), 1);
+    // 删除该文档，历史版本一并删除
+    remove_test_doc(&conn, user, doc_id).await;
+    assert!(get_versions_by_txt_id(&conn, doc_id).await.unwrap().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn metadata_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_metadata", "metadata").await;
+
+    // 升级前上传的文档没有大小，由原文件补上
+    write_file("metadata", "升级前的文档".as_bytes()).await.unwrap();
+    let now = Utc::now();
+    update_txt_metadata(&conn, doc_id, 0, 0, now, now).await.unwrap();
+    assert!(backfill_metadata(&conn).await.unwrap() >= 1);
     let doc = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
-    delete_txt_info(&conn, doc).await.unwrap();
-    // 再次删除，应该成功
+    assert_eq!(doc.size_bytes, "升级前的文档".len() as u64);
+    assert_eq!(doc.char_count, 6);
+    assert!(doc.created_at <= now);
+
+    delete_file("metadata").await.unwrap();
+    remove_test_doc(&conn, user, doc_id).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn trash_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_trash", "trash").await;
+
+    let doc = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    trash_txt(&conn, doc).await.unwrap();
+    assert!(get_txt_by_id(&conn, doc_id).await.unwrap().is_none());
+    let doc = get_trashed_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    restore_txt(&conn, doc).await.unwrap();
+    assert!(get_txt_by_id(&conn, doc_id).await.unwrap().is_some());
+
+    // 文件已经不在磁盘上时仍然可以彻底删除
+    let doc = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    trash_txt(&conn, doc).await.unwrap();
+    let doc = get_trashed_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    purge_txt(&conn, doc).await.unwrap();
+    assert!(get_trashed_txt_by_id(&conn, doc_id).await.unwrap().is_none());
+
     delete_user(&conn, user, None).await.expect("Delete User Failure");
-    
-    println!("Delete User Ok");
+    Ok(())
+}
+
+#[tokio::test]
+async fn near_duplicate_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_simhash", "simhash").await;
+
+    // 指纹相同的文档是近似重复
+    let near = find_near_duplicates(&conn, simhash("simhash").unwrap()).await.unwrap();
+    assert!(near.iter().any(|doc| doc.id == doc_id));
+
+    remove_test_doc(&conn, user, doc_id).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tag_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_tag", "tag").await;
+
+    // 标签，文档已有的忽略
+    let tags = vec!["测试".to_string(), "rust".to_string()];
+    assert_eq!(add_txt_tags(&conn, doc_id, &tags).await.unwrap(), 2);
+    assert_eq!(add_txt_tags(&conn, doc_id, &tags).await.unwrap(), 0);
+    assert_eq!(remove_txt_tag(&conn, doc_id, "测试").await.unwrap(), 1);
+    assert_eq!(get_tags_by_txt_id(&conn, doc_id).await.unwrap(), vec!["rust".to_string()]);
+
+    // 删除文档时标签一并删除
+    remove_test_doc(&conn, user, doc_id).await;
+    assert!(get_tags_by_txt_id(&conn, doc_id).await.unwrap().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn folder_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_folder", "folder").await;
+
+    // 文件夹，删除时文档移到move_to
+    let parent = add_folder(&conn, "测试", user.id, None, 0).await.unwrap();
+    let child = add_folder(&conn, "子文件夹", user.id, Some(parent), 0).await.unwrap();
+    assert_eq!(get_folder_path(&conn, child).await.unwrap(), vec![parent, child]);
+    // 只列出所有者的文件夹
+    let folders = get_folders_by_parent(&conn, Some(parent), user.id).await.unwrap();
+    assert_eq!(folders.iter().map(|f| f.id).collect::<Vec<_>>(), vec![child]);
+    assert!(get_folders_by_parent(&conn, Some(parent), user.id + 1).await.unwrap().is_empty());
+    let txt = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    move_txt_to_folder(&conn, txt, Some(child)).await.unwrap();
+    assert!(!folder_is_empty(&conn, child).await.unwrap());
+    let folder = get_folder_by_id(&conn, child).await.unwrap().unwrap();
+    delete_folder(&conn, folder, Some(parent)).await.unwrap();
+    assert_eq!(get_txt_by_id(&conn, doc_id).await.unwrap().unwrap().folder_id, Some(parent));
+    let folder = get_folder_by_id(&conn, parent).await.unwrap().unwrap();
+    delete_folder(&conn, folder, None).await.unwrap();
+    assert_eq!(get_txt_by_id(&conn, doc_id).await.unwrap().unwrap().folder_id, None);
+    // 顶层只列出用户自己的文档
+    let top = get_txt_by_folder_id(&conn, None, user.id, 0, &[]).await.unwrap();
+    assert!(top.iter().any(|doc| doc.id == doc_id));
+    let others = get_txt_by_folder_id(&conn, None, user.id + 1, 0, &[]).await.unwrap();
+    assert!(others.iter().all(|doc| doc.id != doc_id));
+
+    remove_test_doc(&conn, user, doc_id).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn grant_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_grant_owner", "grant").await;
+
+    // 授权，过期的不算
+    let other = add_user(&conn, "test_grant", "test", 0, false).await.unwrap();
+    assert!(!has_grant(&conn, doc_id, other, false).await.unwrap());
+    let expired = NewGrant {
+        user_id: Some(other),
+        group_id: None,
+        can_write: true,
+        expires_at: Some(Utc::now() - Duration::days(1)),
+    };
+    add_grant(&conn, doc_id, &expired).await.unwrap();
+    assert!(!has_grant(&conn, doc_id, other, false).await.unwrap());
+    // 授权给用户所在的用户组
+    let group = add_group(&conn, "测试组").await.unwrap();
+    assert_eq!(add_group_member(&conn, group, other).await.unwrap(), 1);
+    let read = NewGrant {
+        user_id: None,
+        group_id: Some(group),
+        can_write: false,
+        expires_at: None,
+    };
+    add_grant(&conn, doc_id, &read).await.unwrap();
+    assert!(has_grant(&conn, doc_id, other, false).await.unwrap());
+    assert!(!has_grant(&conn, doc_id, other, true).await.unwrap());
+    assert_eq!(get_granted_txt_ids(&conn, other, false).await.unwrap(), vec![doc_id]);
+    // 删除用户组和用户时，授权一并删除
+    let group = get_group_by_id(&conn, group).await.unwrap().unwrap();
+    delete_group(&conn, group).await.unwrap();
+    assert!(get_granted_txt_ids(&conn, other, false).await.unwrap().is_empty());
+    let other = get_user_by_id(&conn, other).await.unwrap().unwrap();
+    delete_user(&conn, other, None).await.unwrap();
+    assert_eq!(get_grants_by_txt_id(&conn, doc_id).await.unwrap().len(), 0);
+
+    remove_test_doc(&conn, user, doc_id).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn stopword_test() -> Result<()> {
+    let conn = connect().await;
+
+    // 停用词增删测试，重复添加的忽略
+    let words = vec!["测试停用词".to_string()];
+    assert_eq!(add_stopwords(&conn, &words).await.unwrap(), 1);
+    assert_eq!(add_stopwords(&conn, &words).await.unwrap(), 0);
+    assert!(get_all_stopwords(&conn).await.unwrap().contains(&words[0]));
+    assert_eq!(delete_stopword(&conn, &words[0]).await.unwrap(), 1);
+    assert_eq!(delete_stopword(&conn, &words[0]).await.unwrap(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_dict_test() -> Result<()> {
+    let conn = connect().await;
+
+    // 用户词典，重复保存时更新词频
+    save_user_word(&conn, "测试词", None, None).await.unwrap();
+    save_user_word(&conn, "测试词", Some(100), Some("nz".to_string())).await.unwrap();
+    let word = get_all_user_words(&conn).await.unwrap().into_iter().find(|w| w.word == "测试词").unwrap();
+    assert_eq!(word.freq, Some(100));
+    assert_eq!(delete_user_word(&conn, "测试词").await.unwrap(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn synonym_test() -> Result<()> {
+    let conn = connect().await;
+
+    // 同义词，重复添加的忽略
+    let pairs = vec![("测试甲".to_string(), "测试乙".to_string())];
+    assert_eq!(add_synonyms(&conn, &pairs).await.unwrap(), 1);
+    assert_eq!(add_synonyms(&conn, &pairs).await.unwrap(), 0);
+    let synonym = get_all_synonyms(&conn).await.unwrap().into_iter().find(|s| s.word == "测试甲").unwrap();
+    assert_eq!(synonym.synonym, "测试乙");
+    assert_eq!(delete_synonym(&conn, synonym.id).await.unwrap(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn downgraded_owner_test() -> Result<()> {
+    let conn = connect().await;
+    let (user, doc_id) = add_test_doc(&conn, "test_downgraded", "downgraded").await;
+    let doc = get_txt_by_id(&conn, doc_id).await.unwrap().unwrap();
+    let doc = update_doc_info(&conn, doc, None, Some(5)).await.unwrap();
+    let state = AppState {
+        conn: conn.clone(),
+        search: SearchService::create_in_ram().await?,
+    };
+    let claims = |level: u8| -> Claims {
+        serde_json::from_value(serde_json::json!({
+            "exp": 0,
+            "id": user.id,
+            "username": user.username,
+            "is_admin": 0,
+            "level": level,
+        }))
+        .unwrap()
+    };
 
+    // level足够时，所有者可以查看、修改和管理文档
+    let owner = claims(5);
+    assert!(visible_doc(&conn, &owner, Some(doc.clone())).await.is_ok());
+    assert!(writable_doc(&conn, &owner, Some(doc.clone())).await.is_ok());
+    assert!(owned_doc(&state, &owner, doc_id).await.is_ok());
+    // level降低后，查看（详情、下载、版本、相似文档）、修改（更新、删除、上传和恢复版本）
+    // 和管理（授权、标签、移动）都不允许
+    let downgraded = claims(0);
+    let hidden = |res: Result<_, Error>| matches!(res, Err(Error::NoSuchFile));
+    assert!(hidden(visible_doc(&conn, &downgraded, Some(doc.clone())).await));
+    assert!(hidden(writable_doc(&conn, &downgraded, Some(doc.clone())).await));
+    assert!(hidden(owned_doc(&state, &downgraded, doc_id).await));
 
+    remove_test_doc(&conn, user, doc_id).await;
     Ok(())
-}
\ No newline at end of file
+}
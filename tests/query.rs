@@ -5,7 +5,7 @@ use core::time;
 use std::thread::sleep;
 
 use anyhow::Result;
-use ks_backend::database::{db::get_db, mutation::write_file, query::{get_txt_maxlevel_by_userid, read_file}, search::{commiting, init_index, rebuild_search_index, search_from_rev_index, SearchField}};
+use ks_backend::database::{db::get_db, mutation::write_file, query::{get_txt_by_ids, get_txt_maxlevel_by_userid, read_file}, search::{SearchField, SearchService}};
 use ring::digest::{Context, SHA256};
 use data_encoding::HEXUPPER;
 
@@ -39,6 +39,11 @@ async fn query_test() -> Result<()> {
     //
     let res = get_txt_maxlevel_by_userid(&conn, 3).await.unwrap();
     println!("{res:?}");
+    //
+    let res = get_txt_by_ids(&conn, &[1, 2, 3]).await.unwrap();
+    println!("{res:?}");
+    let res = get_txt_by_ids(&conn, &[]).await.unwrap();
+    assert!(res.is_empty());
 
     //
     let sc = "你好";
@@ -8,28 +8,66 @@ use data_encoding::HEXUPPER;
 use httpc_test::*;
 use ks_backend::{
     database::{
+        analyzer::AnalyzerOptions,
         db::get_db,
         mutation::write_file,
         query::get_txt_by_id,
-        search::{commiting, init_index, rebuild_search_index, search_from_rev_index, SearchField},
+        stopword::parse_stopwords,
+        search::{
+            DocMeta, SearchField, SearchFilter, SearchOptions, SearchService, SnippetOptions, Sort,
+            SortBy, SortOrder, MAX_QUERY_WINDOW,
+        },
     },
-    entities::txt,
+    entities::{synonym, txt, user_dict},
+    web::tag::normalize_tag,
 };
 use ring::digest::{Context, SHA256};
-use sea_orm::DatabaseConnection;
+use sea_orm::{prelude::DateTimeUtc, DatabaseConnection};
+
+fn new_txt(id: u64, title: &str, level: u8) -> txt::Model {
+    let now = DateTimeUtc::default();
+    txt::Model {
+        id,
+        title: title.to_string(),
+        hash: format!("{id}"),
+        user_id: 1,
+        level,
+        format: "plain".to_string(),
+        encoding: None,
+        deleted_at: None,
+        created_at: now,
+        updated_at: now,
+        size_bytes: 0,
+        char_count: 0,
+        simhash: None,
+        folder_id: None,
+        simhash_skipped: 0,
+    }
+}
+
+fn search_options(field: SearchField, level: u8, offset: usize, limit: usize) -> SearchOptions {
+    SearchOptions {
+        field,
+        level,
+        offset,
+        limit,
+        ..Default::default()
+    }
+}
 
 async fn search_test(
+    search: &SearchService,
     conn: &DatabaseConnection,
     field: SearchField,
     q: &str,
     level: u8,
     limit: usize,
 ) -> anyhow::Result<Vec<txt::Model>> {
-    let res = search_from_rev_index(field, "红色 燃烧", level, limit)?;
+    let res = search.search("红色 燃烧", &search_options(field, level, 0, limit))?;
     let mut docs = Vec::new();
 
-    for (id, _) in res {
-        let doc = get_txt_by_id(conn, id).await?;
+    for hit in res.hits {
+        let doc = get_txt_by_id(conn, hit.id).await?;
         match doc {
             Some(doc) => docs.push(doc),
             None => (),
@@ -41,7 +79,7 @@ async fn search_test(
 
 #[tokio::test]
 async fn search() -> Result<()> {
-    init_index();
+    let search = SearchService::create_in_ram().await?;
 
     println!("Connect to DataBase...");
     let conn: sea_orm::prelude::DatabaseConnection = match get_db().await {
@@ -50,13 +88,13 @@ async fn search() -> Result<()> {
     };
     println!("DataBase Connected ...");
 
-    rebuild_search_index(conn.clone()).await;
-    tokio::spawn(commiting());
+    search.rebuild(&conn).await?;
+    tokio::spawn(search.clone().commiting());
     sleep(time::Duration::from_secs(5));
 
     println!("搜索功能测试");
     println!("---");
-    match search_test(&conn, SearchField::All, "+红色 -街道", 255, 2).await {
+    match search_test(&search, &conn, SearchField::All, "+红色 -街道", 255, 2).await {
         Ok(res) => {
             for e in res {
                 println!("{e:?}");
@@ -65,7 +103,7 @@ async fn search() -> Result<()> {
         Err(e) => println!("{e:?}"),
     }
     println!("---");
-    match search_test(&conn, SearchField::Body, "路西恩", 255, 0).await {
+    match search_test(&search, &conn, SearchField::Body, "路西恩", 255, 20).await {
         Ok(res) => {
             for e in res {
                 println!("{e:?}");
@@ -74,7 +112,7 @@ async fn search() -> Result<()> {
         Err(e) => println!("{e:?}"),
     }
     println!("---");
-    match search_test(&conn, SearchField::Body, "路西*", 64, 0).await {
+    match search_test(&search, &conn, SearchField::Body, "路西*", 64, 20).await {
         Ok(res) => {
             for e in res {
                 println!("{e:?}");
@@ -84,7 +122,7 @@ async fn search() -> Result<()> {
     }
 
     println!("---");
-    match search_test(&conn, SearchField::Body, "*", 64, 0).await {
+    match search_test(&search, &conn, SearchField::Body, "*", 64, 20).await {
         Ok(res) => {
             for e in res {
                 println!("{e:?}");
@@ -95,3 +133,627 @@ async fn search() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn search_services_in_parallel() -> Result<()> {
+    // 同一进程中的两个索引互不影响
+    let a = SearchService::create_in_ram().await?;
+    let b = SearchService::create_in_ram().await?;
+
+    a.add_doc(&new_txt(1, "路西恩", 0), "红色的火焰在燃烧".to_string()).await?;
+    b.add_doc(&new_txt(2, "街道", 0), "安静的街道".to_string()).await?;
+    a.commit().await?;
+    b.commit().await?;
+
+    assert_eq!(a.search("燃烧", &search_options(SearchField::All, 0, 0, 10))?.hits.len(), 1);
+    assert_eq!(b.search("燃烧", &search_options(SearchField::All, 0, 0, 10))?.hits.len(), 0);
+    assert_eq!(b.search("街道", &search_options(SearchField::Title, 0, 0, 10))?.hits[0].id, 2);
+
+    a.delete_doc(1).await?;
+    a.commit().await?;
+    assert_eq!(a.count_doc(255)?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_highlight() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search
+        .add_doc(&new_txt(1, "路西恩", 0), "红色的火焰在燃烧".to_string())
+        .await?;
+    search.commit().await?;
+
+    let options = SearchOptions {
+        snippet: Some(SnippetOptions {
+            fragment_size: 50,
+            pre_tag: "[".to_string(),
+            post_tag: "]".to_string(),
+        }),
+        ..search_options(SearchField::All, 0, 0, 10)
+    };
+    let hits = search.search("燃烧", &options)?.hits;
+    let highlight = hits[0].highlight.clone().unwrap();
+    assert_eq!(highlight.title, None);
+    assert_eq!(highlight.body.unwrap(), "红色的火焰在[燃烧]");
+
+    let hits = search.search("燃烧", &search_options(SearchField::All, 0, 0, 10))?.hits;
+    assert_eq!(hits[0].highlight, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_pagination() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    for id in 1..=5 {
+        search
+            .add_doc(&new_txt(id, &format!("文档{id}"), id as u8), "红色的火焰在燃烧".to_string())
+            .await?;
+    }
+    search.commit().await?;
+
+    // 只统计命中数
+    let page = search.search("燃烧", &search_options(SearchField::Body, 4, 0, 0))?;
+    assert_eq!(page.total, 4);
+    assert!(page.hits.is_empty());
+
+    let first = search.search("燃烧", &search_options(SearchField::Body, 4, 0, 3))?;
+    let second = search.search("燃烧", &search_options(SearchField::Body, 4, 3, 3))?;
+    assert_eq!(first.total, 4);
+    assert_eq!(second.total, 4);
+    assert_eq!(first.hits.len(), 3);
+    assert_eq!(second.hits.len(), 1);
+    assert!(first.hits.iter().all(|hit| hit.id != second.hits[0].id));
+
+    // 过大的offset返回错误，而不是溢出或分配过多的内存
+    let last = search_options(SearchField::Body, 4, MAX_QUERY_WINDOW - 3, 3);
+    assert!(search.search("燃烧", &last)?.hits.is_empty());
+    for offset in [MAX_QUERY_WINDOW, usize::MAX] {
+        assert!(search.search("燃烧", &search_options(SearchField::Body, 4, offset, 3)).is_err());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_sort() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let titles = ["乙", "甲", "丙"];
+    for (i, title) in titles.iter().enumerate() {
+        let id = i as u64 + 1;
+        let mut txt = new_txt(id, title, id as u8);
+        txt.user_id = 4 - id;
+        txt.size_bytes = id * 100;
+        search.add_doc(&txt, "红色的火焰在燃烧".to_string()).await?;
+    }
+    search.commit().await?;
+
+    let sorted = |by: SortBy, order: SortOrder| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            sort: Sort { by, order },
+            ..search_options(SearchField::Body, 255, 0, 10)
+        };
+        Ok(search.search("燃烧", &options)?.hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert_eq!(sorted(SortBy::Level, SortOrder::Desc)?, vec![3, 2, 1]);
+    assert_eq!(sorted(SortBy::Level, SortOrder::Asc)?, vec![1, 2, 3]);
+    assert_eq!(sorted(SortBy::Owner, SortOrder::Asc)?, vec![3, 2, 1]);
+    assert_eq!(sorted(SortBy::Size, SortOrder::Desc)?, vec![3, 2, 1]);
+    // 按标题的UTF-8字节序：丙(E4B899) < 乙(E4B999) < 甲(E794B2)
+    assert_eq!(sorted(SortBy::Title, SortOrder::Asc)?, vec![3, 1, 2]);
+
+    // 分页时也按排序键
+    let options = SearchOptions {
+        sort: Sort {
+            by: SortBy::Level,
+            order: SortOrder::Asc,
+        },
+        ..search_options(SearchField::Body, 255, 1, 1)
+    };
+    assert_eq!(search.search("燃烧", &options)?.hits[0].id, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_filters() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let day = |d: i64| {
+        "2024-01-01T00:00:00Z".parse::<DateTimeUtc>().unwrap() + chrono::Duration::days(d)
+    };
+    for id in 1..=4u64 {
+        let mut txt = new_txt(id, &format!("文档{id}"), id as u8);
+        txt.user_id = id % 2 + 1;
+        txt.created_at = day(id as i64);
+        txt.format = if id <= 2 { "plain" } else { "markdown" }.to_string();
+        search.add_doc(&txt, "红色的火焰在燃烧".to_string()).await?;
+    }
+    search.commit().await?;
+
+    let filtered = |filter: SearchFilter| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            filter,
+            sort: Sort {
+                by: SortBy::Level,
+                order: SortOrder::Asc,
+            },
+            ..search_options(SearchField::Body, 3, 0, 10)
+        };
+        let page = search.search("燃烧", &options)?;
+        assert_eq!(page.total, page.hits.len());
+        Ok(page.hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert_eq!(filtered(SearchFilter::default())?, vec![1, 2, 3]);
+    let owners = SearchFilter {
+        owners: vec![1],
+        ..Default::default()
+    };
+    assert_eq!(filtered(owners)?, vec![2]);
+    // 上限不能超过用户自己的level
+    let levels = SearchFilter {
+        min_level: Some(2),
+        max_level: Some(200),
+        ..Default::default()
+    };
+    assert_eq!(filtered(levels)?, vec![2, 3]);
+    let dates = SearchFilter {
+        created_from: Some(day(2)),
+        created_to: Some(day(3)),
+        ..Default::default()
+    };
+    assert_eq!(filtered(dates)?, vec![2]);
+    let formats = SearchFilter {
+        formats: vec!["markdown".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(filtered(formats)?, vec![3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_tags() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| t.to_string()).collect() };
+    let meta = |t: &[&str]| DocMeta {
+        tags: tags(t),
+        ..Default::default()
+    };
+    let body = || "红色的火焰在燃烧".to_string();
+    search.add_doc_with_meta(&new_txt(1, "文档1", 0), &meta(&["小说", "奇幻"]), body()).await?;
+    search.add_doc_with_meta(&new_txt(2, "文档2", 0), &meta(&["小说"]), body()).await?;
+    search.add_doc_with_meta(&new_txt(3, "文档3", 0), &meta(&["历史"]), body()).await?;
+    search.add_doc_with_meta(&new_txt(4, "文档4", 9), &meta(&["历史"]), body()).await?;
+    search.commit().await?;
+
+    let options = |tags: Vec<String>, limit: usize| SearchOptions {
+        filter: SearchFilter {
+            tags,
+            ..Default::default()
+        },
+        sort: Sort {
+            by: SortBy::Level,
+            order: SortOrder::Asc,
+        },
+        ..search_options(SearchField::Body, 0, 0, limit)
+    };
+
+    // 标签计数只包括用户可以查看的命中
+    let page = search.search("燃烧", &options(Vec::new(), 10))?;
+    let counts: Vec<(&str, u64)> = page.tags.iter().map(|t| (t.tag.as_str(), t.count)).collect();
+    assert_eq!(counts, vec![("小说", 2), ("历史", 1), ("奇幻", 1)]);
+
+    // 有任意一个标签即可
+    let page = search.search("燃烧", &options(tags(&["奇幻", "历史"]), 10))?;
+    let ids: Vec<u64> = page.hits.iter().map(|hit| hit.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+    let page = search.search("燃烧", &options(tags(&["小说"]), 0))?;
+    assert_eq!(page.total, 2);
+    assert_eq!(page.tags.len(), 2);
+
+    // 非ASCII的大写字母在保存和过滤时同样转为小写
+    let meta = DocMeta {
+        tags: vec![normalize_tag("Ärger")?, normalize_tag("ΣΟΦΙΑ")?],
+        ..Default::default()
+    };
+    search.add_doc_with_meta(&new_txt(5, "文档5", 0), &meta, body()).await?;
+    search.commit().await?;
+    for tag in [" ÄRGER", "Σοφια"] {
+        let page = search.search("燃烧", &options(vec![normalize_tag(tag)?], 10))?;
+        let ids: Vec<u64> = page.hits.iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, vec![5]);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_grants() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    for (id, level) in [(1, 0), (2, 5), (3, 9)] {
+        let txt = new_txt(id, &format!("文档{id}"), level);
+        search.add_doc(&txt, "红色的火焰在燃烧".to_string()).await?;
+    }
+    search.commit().await?;
+
+    let ids = |granted: Vec<u64>, filter: SearchFilter| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            granted,
+            filter,
+            ..search_options(SearchField::Body, 0, 0, 10)
+        };
+        let page = search.search("燃烧", &options)?;
+        assert_eq!(page.total, page.hits.len());
+        let mut ids: Vec<u64> = page.hits.iter().map(|hit| hit.id).collect();
+        ids.sort_unstable();
+        Ok(ids)
+    };
+    assert_eq!(ids(Vec::new(), SearchFilter::default())?, vec![1]);
+    // 授权的文档不受level限制
+    assert_eq!(ids(vec![3], SearchFilter::default())?, vec![1, 3]);
+    // level的过滤条件对授权的文档同样有效
+    let levels = SearchFilter {
+        min_level: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(ids(vec![2, 3], levels)?, vec![2, 3]);
+    let levels = SearchFilter {
+        max_level: Some(5),
+        ..Default::default()
+    };
+    assert_eq!(ids(vec![2, 3], levels)?, vec![1, 2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_in_folder() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    // 文件夹1下有文件夹5，文档3不在任何文件夹中
+    let paths = [vec![1], vec![1, 5], vec![], vec![2], vec![1, 5]];
+    for (i, path) in paths.iter().enumerate() {
+        let id = i as u64 + 1;
+        let level = if id == 5 { 9 } else { 0 };
+        let meta = DocMeta {
+            folder_path: path.clone(),
+            ..Default::default()
+        };
+        let txt = new_txt(id, &format!("文档{id}"), level);
+        search.add_doc_with_meta(&txt, &meta, "红色的火焰在燃烧".to_string()).await?;
+    }
+    search.commit().await?;
+
+    let in_folder = |path: Vec<u64>| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            filter: SearchFilter {
+                folder_path: Some(path),
+                ..Default::default()
+            },
+            ..search_options(SearchField::Body, 0, 0, 10)
+        };
+        let page = search.search("燃烧", &options)?;
+        let mut ids: Vec<u64> = page.hits.iter().map(|hit| hit.id).collect();
+        ids.sort_unstable();
+        Ok(ids)
+    };
+    // 包括子文件夹中的文档，不包括level更高的
+    assert_eq!(in_folder(vec![1])?, vec![1, 2]);
+    assert_eq!(in_folder(vec![1, 5])?, vec![2]);
+    assert_eq!(in_folder(vec![2])?, vec![4]);
+    assert!(in_folder(vec![5])?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_fuzzy() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "索引", 0), "tantivy 是一个搜索引擎".to_string()).await?;
+    search.add_doc(&new_txt(2, "拼写", 0), "tantivx 拼错了".to_string()).await?;
+    search.commit().await?;
+
+    let fuzzy = |q: &str, fuzzy: Option<u8>| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            fuzzy,
+            ..search_options(SearchField::Body, 0, 0, 10)
+        };
+        Ok(search.search(q, &options)?.hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert!(fuzzy("tantivz", None)?.is_empty());
+    assert_eq!(fuzzy("tantivz", Some(1))?.len(), 2);
+    // 精确命中排在模糊命中之前
+    assert_eq!(fuzzy("tantivy", Some(1))?, vec![1, 2]);
+    assert_eq!(fuzzy("tantivx", Some(1))?, vec![2, 1]);
+    assert!(fuzzy("tantzzz", Some(2))?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_pinyin() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "路西恩", 0), "红色的火焰在燃烧".to_string()).await?;
+    search.add_doc(&new_txt(2, "街道", 0), "安静的街道".to_string()).await?;
+    search.commit().await?;
+
+    let pinyin = |q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::Pinyin, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert_eq!(pinyin("luxien")?, vec![1]);
+    assert_eq!(pinyin("lxe")?, vec![1]);
+    assert_eq!(pinyin("ranshao")?, vec![1]);
+    assert_eq!(pinyin("JieDao")?, vec![2]);
+    // 汉字也会转为拼音后检索
+    assert_eq!(pinyin("街道")?, vec![2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_pinyin_across_words() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.set_stopwords(vec!["的".to_string(), "了".to_string()]);
+    search.add_doc(&new_txt(1, "国庆", 0), "中华人民共和国成立了".to_string()).await?;
+    search.add_doc(&new_txt(2, "火焰", 0), "红色的火焰在燃烧".to_string()).await?;
+    search.commit().await?;
+
+    let pinyin = |q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::Pinyin, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+    // jieba搜索模式输出的重叠的词不影响音节的位置
+    assert_eq!(pinyin("zhonghuarenmingongheguo")?, vec![1]);
+    assert_eq!(pinyin("renmingongheguochengli")?, vec![1]);
+    assert_eq!(pinyin("zhrmghg")?, vec![1]);
+    // 停用词在拼音域中保留，短语可以跨过它
+    assert_eq!(pinyin("hongsedehuoyan")?, vec![2]);
+    assert_eq!(pinyin("chenglile")?, vec![1]);
+    assert!(pinyin("hongsehuoyan")?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_traditional_chinese() -> Result<()> {
+    let folded = SearchService::create_in_ram().await?;
+    let unfolded = SearchService::create_in_ram_with(AnalyzerOptions {
+        fold_traditional: false,
+        ..Default::default()
+    })
+    .await?;
+    for search in [&folded, &unfolded] {
+        search.add_doc(&new_txt(1, "路西恩", 0), "紅色的火焰在燃燒".to_string()).await?;
+        search.add_doc(&new_txt(2, "街道", 0), "安静的街道".to_string()).await?;
+        search.commit().await?;
+    }
+
+    let ids = |search: &SearchService, q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::Body, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert_eq!(ids(&folded, "燃烧")?, vec![1]);
+    assert_eq!(ids(&folded, "安靜")?, vec![2]);
+    assert!(ids(&unfolded, "燃烧")?.is_empty());
+    assert_eq!(ids(&unfolded, "燃燒")?, vec![1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_mixed_language() -> Result<()> {
+    let stemmed = SearchService::create_in_ram().await?;
+    let unstemmed = SearchService::create_in_ram_with(AnalyzerOptions {
+        stem_language: None,
+        ..Default::default()
+    })
+    .await?;
+    for search in [&stemmed, &unstemmed] {
+        search.add_doc(&new_txt(1, "Running", 0), "狗在公园里Running".to_string()).await?;
+        search.add_doc(&new_txt(2, "Café", 0), "一家小Café".to_string()).await?;
+        search.commit().await?;
+    }
+
+    let ids = |search: &SearchService, q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::All, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+
+    assert_eq!(ids(&stemmed, "run")?, vec![1]);
+    assert_eq!(ids(&stemmed, "RUNS")?, vec![1]);
+    assert_eq!(ids(&stemmed, "公园")?, vec![1]);
+    assert_eq!(ids(&stemmed, "cafe")?, vec![2]);
+    assert!(ids(&unstemmed, "run")?.is_empty());
+    assert_eq!(ids(&unstemmed, "running")?, vec![1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_phrase_with_punctuation() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "问候", 0), "Hello, world! 你好，世界".to_string()).await?;
+    search.add_doc(&new_txt(2, "告别", 0), "hello big world".to_string()).await?;
+    search.commit().await?;
+
+    let ids = |q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::Body, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+    // 短语中间的标点和空白不影响匹配
+    assert_eq!(ids("\"hello world\"")?, vec![1]);
+    assert_eq!(ids("\"hello   world\"")?, vec![1]);
+    assert_eq!(ids("\"你好世界\"")?, vec![1]);
+    assert_eq!(ids("\"world 你好\"")?, vec![1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_stopwords() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.set_stopwords(parse_stopwords("火焰 THE\n的"));
+    search.add_doc(&new_txt(1, "路西恩", 0), "红色的火焰在燃烧 the fire".to_string()).await?;
+    search.commit().await?;
+
+    let count = |q: &str| -> Result<usize> {
+        Ok(search.search(q, &search_options(SearchField::Body, 0, 0, 10))?.total)
+    };
+    assert_eq!(count("火焰")?, 0);
+    assert_eq!(count("The")?, 0);
+    assert_eq!(count("燃烧")?, 1);
+    assert_eq!(count("fire")?, 1);
+    assert_eq!(parse_stopwords(" b a\tb\n"), vec!["a", "b"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_user_dict() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let word = user_dict::Model {
+        id: 1,
+        word: "星火智聊".to_string(),
+        freq: Some(1000),
+        tag: Some("nz".to_string()),
+    };
+    search.set_user_dict(&[word]);
+    search.add_doc(&new_txt(1, "产品", 0), "新产品星火智聊上线了".to_string()).await?;
+    search.add_doc(&new_txt(2, "夜空", 0), "夜空中的星火".to_string()).await?;
+    search.commit().await?;
+
+    let ids = |q: &str| -> Result<Vec<u64>> {
+        let hits = search.search(q, &search_options(SearchField::Body, 0, 0, 10))?.hits;
+        Ok(hits.iter().map(|hit| hit.id).collect())
+    };
+    // 词典中的词作为一个整体匹配
+    assert_eq!(ids("星火智聊")?, vec![1]);
+    assert_eq!(ids("星火")?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_with_synonyms() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let synonym = |id: u64, word: &str, synonym: &str| synonym::Model {
+        id,
+        word: word.to_string(),
+        synonym: synonym.to_string(),
+    };
+    search.set_synonyms(&[synonym(1, "电脑", "计算机"), synonym(2, "计算机", "电脑")]);
+    search.add_doc(&new_txt(1, "电脑", 0), "买了一台新电脑".to_string()).await?;
+    search.add_doc(&new_txt(2, "计算机", 0), "买了一台新计算机".to_string()).await?;
+    search.add_doc(&new_txt(3, "旧计算机", 0), "卖掉旧计算机".to_string()).await?;
+    search.commit().await?;
+
+    let ids = |q: &str, synonyms: bool| -> Result<Vec<u64>> {
+        let options = SearchOptions {
+            synonyms,
+            ..search_options(SearchField::Body, 0, 0, 10)
+        };
+        Ok(search.search(q, &options)?.hits.iter().map(|hit| hit.id).collect())
+    };
+    // 同义词命中排在原词命中之后
+    let hits = ids("电脑", true)?;
+    assert_eq!((hits.len(), hits[0]), (3, 1));
+    let hits = ids("计算机", true)?;
+    assert_eq!((hits.len(), hits[2]), (3, 1));
+    assert_eq!(ids("电脑", false)?, vec![1]);
+    // 排除的词不扩展
+    assert!(ids("买 -电脑", true)?.contains(&2));
+    // 同义词命中时查询中排除和必需的词仍然有效
+    assert_eq!(ids("电脑 -买", true)?, vec![3]);
+    assert_eq!(ids("电脑 +旧", true)?, vec![3]);
+    let mut required = ids("+电脑 +买", true)?;
+    required.sort_unstable();
+    assert_eq!(required, vec![1, 2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_similar() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "火焰", 0), "红色的火焰在夜空中燃烧".to_string()).await?;
+    search.add_doc(&new_txt(2, "大火", 0), "火焰燃烧了整个夜空".to_string()).await?;
+    search.add_doc(&new_txt(3, "机密", 9), "夜空中的火焰燃烧".to_string()).await?;
+    search.add_doc(&new_txt(4, "街道", 0), "安静街道".to_string()).await?;
+    search.commit().await?;
+
+    let ids = |id: u64, level: u8| -> Result<Option<Vec<u64>>> {
+        let hits = search.similar(id, level, &[], 10)?;
+        Ok(hits.map(|hits| hits.iter().map(|hit| hit.id).collect()))
+    };
+    // 不含自己和level更高的文档
+    assert_eq!(ids(1, 0)?, Some(vec![2]));
+    let mut all = ids(1, 9)?.unwrap();
+    all.sort_unstable();
+    assert_eq!(all, vec![2, 3]);
+    assert_eq!(ids(5, 9)?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_suggest() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "火焰之歌", 0), "火焰燃烧".to_string()).await?;
+    search.add_doc(&new_txt(2, "火山", 0), "火山爆发，火焰冲天".to_string()).await?;
+    search.add_doc(&new_txt(3, "火星计划", 9), "火星探测".to_string()).await?;
+    search.add_doc(&new_txt(4, "Rust Guide", 0), "rustacean".to_string()).await?;
+    search.commit().await?;
+
+    let suggest = search.suggest("火", 0, &[], 5)?;
+    let mut titles: Vec<&str> = suggest.titles.iter().map(|t| t.title.as_str()).collect();
+    titles.sort_unstable();
+    assert_eq!(titles, vec!["火山", "火焰之歌"]);
+    // 火焰出现在两个文档中，排在最前；不含level更高的文档中的词
+    assert_eq!(suggest.terms[0].term, "火焰");
+    assert_eq!(suggest.terms[0].count, 2);
+    assert!(suggest.terms.iter().all(|t| t.term != "火星"));
+    assert_eq!(search.suggest("火", 9, &[], 5)?.titles.len(), 3);
+
+    let suggest = search.suggest("RUST", 0, &[], 5)?;
+    assert_eq!(suggest.titles[0].id, 4);
+    assert!(suggest.terms.iter().any(|t| t.term == "rust"));
+    assert!(search.suggest(" ", 0, &[], 5)?.titles.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn suggest_and_similar_with_grants() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    search.add_doc(&new_txt(1, "火焰", 0), "红色的火焰在夜空中燃烧".to_string()).await?;
+    search.add_doc(&new_txt(2, "火星计划", 9), "火焰燃烧夜空".to_string()).await?;
+    search.add_doc(&new_txt(3, "火山", 9), "火焰燃烧夜空".to_string()).await?;
+    search.commit().await?;
+
+    // 只有授权的文档可见
+    let suggest = search.suggest("火", 0, &[2], 5)?;
+    let mut titles: Vec<&str> = suggest.titles.iter().map(|t| t.title.as_str()).collect();
+    titles.sort_unstable();
+    assert_eq!(titles, vec!["火星计划", "火焰"]);
+    let count = |term: &str| suggest.terms.iter().find(|t| t.term == term).map(|t| t.count);
+    assert_eq!(count("火焰"), Some(2));
+    assert_eq!(count("火星"), Some(1));
+    assert_eq!(count("火山"), None);
+
+    let ids: Vec<u64> = search.similar(1, 0, &[3], 10)?.unwrap().iter().map(|hit| hit.id).collect();
+    assert_eq!(ids, vec![3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_suggest_folded() -> Result<()> {
+    let search = SearchService::create_in_ram().await?;
+    let long_title = "資料庫設計與實作：從入門到精通的完整指南第二版";
+    search.add_doc(&new_txt(1, long_title, 0), "資料".to_string()).await?;
+    search.add_doc(&new_txt(2, "Runners", 0), "running fast".to_string()).await?;
+    search.commit().await?;
+
+    // 繁体标题按简体前缀补全，超过前缀域长度的前缀也按简体比较
+    assert_eq!(search.suggest("资料库", 0, &[], 5)?.titles.len(), 1);
+    let long_prefix: String = long_title.chars().take(24).collect();
+    assert_eq!(search.suggest(&long_prefix, 0, &[], 5)?.titles.len(), 1);
+    assert_eq!(search.suggest(&fast2s::convert(&long_prefix), 0, &[], 5)?.titles.len(), 1);
+
+    // 补全出原词而不是词干
+    let terms: Vec<String> = search
+        .suggest("runn", 0, &[], 5)?
+        .terms
+        .into_iter()
+        .map(|t| t.term)
+        .collect();
+    assert!(terms.contains(&"running".to_string()));
+    assert!(terms.contains(&"runners".to_string()));
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use ks_backend::database::simhash::{
+    hamming_distance, simhash, simhash_bands, NEAR_DUPLICATE_DISTANCE,
+};
+
+#[test]
+fn simhash_near_duplicates() {
+    let text = "红色的火焰在夜空中燃烧，照亮了整条街道。路西恩站在窗前，看着远处的火光。";
+    let resaved = "红色的火焰在夜空中燃烧，照亮了整条街道。\r\n\r\n  路西恩站在窗前，看着远处的火光。 ";
+    let edited = "红色的火焰在夜空中燃烧，照亮了整条街道。路西恩站在窗前，看着远处的火光！";
+    let other = "安静的街道上没有一个行人，只有风吹过树叶的声音，一切都显得格外平静。";
+
+    let a = simhash(text).unwrap();
+    // 只改变空白和标点的指纹相同
+    assert_eq!(simhash(resaved), Some(a));
+    assert_eq!(simhash(edited), Some(a));
+    assert!(hamming_distance(a, simhash(other).unwrap()) > NEAR_DUPLICATE_DISTANCE);
+
+    // 改动了词语的文本指纹不同，但仍在近似范围内，且至少有一段相同
+    let long = "红色的火焰在夜空中燃烧，照亮了整条街道。路西恩站在窗前，看着远处的火光。人们从四面八方赶来，有的提着水桶，有的拿着铁锹，大家齐心协力想要扑灭这场大火。消防车的警笛声由远及近，街道上挤满了围观的人群。";
+    let b = simhash(long).unwrap();
+    for (from, to) in [("窗前", "门口"), ("大火", "火灾")] {
+        let c = simhash(&long.replacen(from, to, 1)).unwrap();
+        let distance = hamming_distance(b, c);
+        assert!(0 < distance && distance <= NEAR_DUPLICATE_DISTANCE);
+        assert!(simhash_bands(b).iter().zip(simhash_bands(c)).any(|(x, y)| *x == y));
+    }
+    // 改动较多的和无关的文本不是近似重复
+    let rewritten = long.replacen("照亮了整条街道", "照亮了半座城市", 1);
+    assert!(hamming_distance(b, simhash(&rewritten).unwrap()) > NEAR_DUPLICATE_DISTANCE);
+    assert!(hamming_distance(b, simhash(other).unwrap()) > NEAR_DUPLICATE_DISTANCE);
+    // 大小写不影响
+    assert_eq!(simhash("Hello World"), simhash("hello world"));
+    // 没有文字时没有指纹
+    assert_eq!(simhash(" \n，。"), None);
+}
+
+#[test]
+fn simhash_bands_of_near_duplicates() {
+    let a: u64 = 0x0123_4567_89AB_CDEF;
+    assert_eq!(simhash_bands(a), [0xCDEF, 0x89AB, 0x4567, 0x0123]);
+    // 改变不超过NEAR_DUPLICATE_DISTANCE位时至少有一段相同
+    for bits in [[0, 16, 32], [15, 31, 63], [1, 2, 3]] {
+        let b = bits.iter().fold(a, |acc, bit| acc ^ 1 << bit);
+        assert!(hamming_distance(a, b) <= NEAR_DUPLICATE_DISTANCE);
+        let same = simhash_bands(a)
+            .iter()
+            .zip(simhash_bands(b))
+            .any(|(x, y)| *x == y);
+        assert!(same);
+    }
+}